@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+/// Risk level of a tool, tagged when the tool is added to the agent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskLevel {
+    #[default]
+    Low,
+    Medium,
+    High,
+}
+
+/// Decision on a tool call awaiting approval
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalDecision {
+    Approve,
+    /// Reject the call, the reason is fed back to the model as the observation
+    Reject(String),
+    /// Run the call with edited arguments
+    Edit(Value),
+}
+
+#[async_trait]
+pub trait Approver: Send + Sync {
+    async fn review(&self, tool_name: &str, args: &Value) -> ApprovalDecision;
+}
+
+/// A pending approval sent to the receiving side of a [`ChannelApprover`]
+pub struct ApprovalRequest {
+    pub tool_name: String,
+    pub args: Value,
+    pub respond: oneshot::Sender<ApprovalDecision>,
+}
+
+/// Approver forwarding requests over a channel, for services where the decision
+/// is made elsewhere, e.g. by a web UI.
+pub struct ChannelApprover {
+    sender: mpsc::Sender<ApprovalRequest>,
+}
+
+impl ChannelApprover {
+    pub fn new(buffer: usize) -> (Self, mpsc::Receiver<ApprovalRequest>) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        (ChannelApprover { sender }, receiver)
+    }
+}
+
+#[async_trait]
+impl Approver for ChannelApprover {
+    async fn review(&self, tool_name: &str, args: &Value) -> ApprovalDecision {
+        let (respond, decision) = oneshot::channel();
+        let request = ApprovalRequest {
+            tool_name: tool_name.to_string(),
+            args: args.clone(),
+            respond,
+        };
+
+        if self.sender.send(request).await.is_err() {
+            return ApprovalDecision::Reject("No approver is listening".to_string());
+        }
+
+        decision.await.unwrap_or_else(|_| {
+            ApprovalDecision::Reject("Approval request dropped without a decision".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn channel_approver_roundtrip() {
+        let (approver, mut receiver) = ChannelApprover::new(1);
+
+        tokio::spawn(async move {
+            let request = receiver.recv().await.unwrap();
+            assert_eq!(request.tool_name, "run_shell");
+            request
+                .respond
+                .send(ApprovalDecision::Edit(json!({"command": "ls"})))
+                .unwrap();
+        });
+
+        let decision = approver
+            .review("run_shell", &json!({"command": "rm -rf /"}))
+            .await;
+        assert_eq!(decision, ApprovalDecision::Edit(json!({"command": "ls"})));
+    }
+
+    #[tokio::test]
+    async fn reject_without_listener() {
+        let (approver, receiver) = ChannelApprover::new(1);
+        drop(receiver);
+
+        let decision = approver.review("run_shell", &json!({})).await;
+        assert!(matches!(decision, ApprovalDecision::Reject(_)));
+    }
+}
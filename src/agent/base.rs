@@ -13,17 +13,30 @@ use async_openai::{
 use async_trait::async_trait;
 
 #[async_trait]
-pub trait Agent {
+pub trait Agent: Send + Sync {
     fn name(&self) -> &str;
     fn description(&self) -> &str;
 
     async fn step<'a>(&self, message: &'a str) -> Result<String>;
+
+    /// Conversation history, saved when a run is checkpointed
+    async fn history(&self) -> Vec<ChatCompletionRequestMessage> {
+        Vec::new()
+    }
+
+    /// Replace the conversation history, used when a run is resumed
+    async fn restore_history(&self, _messages: Vec<ChatCompletionRequestMessage>) {}
+
+    /// Set or clear instructions on the final answer sent after the system prompt, e.g. the
+    /// schema of a typed answer. They are not part of the history and replace earlier ones.
+    async fn set_answer_prompt(&self, _prompt: Option<&str>) {}
 }
 
 pub struct BaseAgent {
     name: String,
     description: String,
     messages: tokio::sync::Mutex<Vec<ChatCompletionRequestMessage>>,
+    answer_prompt: tokio::sync::Mutex<Option<String>>,
     client: Client<OpenAIConfig>,
     model_name: String,
 }
@@ -65,6 +78,18 @@ impl Agent for BaseAgent {
 
         Ok(result)
     }
+
+    async fn history(&self) -> Vec<ChatCompletionRequestMessage> {
+        self.messages.lock().await.clone()
+    }
+
+    async fn restore_history(&self, messages: Vec<ChatCompletionRequestMessage>) {
+        *self.messages.lock().await = messages;
+    }
+
+    async fn set_answer_prompt(&self, prompt: Option<&str>) {
+        *self.answer_prompt.lock().await = prompt.map(str::to_string);
+    }
 }
 
 impl BaseAgent {
@@ -86,6 +111,7 @@ impl BaseAgent {
             name: name.to_string(),
             description: description.to_string(),
             messages: tokio::sync::Mutex::new(Vec::new()),
+            answer_prompt: tokio::sync::Mutex::new(None),
             client,
             model_name: model_name.to_string(),
         };
@@ -105,19 +131,33 @@ impl BaseAgent {
     }
 
     async fn execute(&self) -> Result<String> {
-        let completion = {
-            let lock = self.messages.lock().await;
-
-            self.client
-                .chat()
-                .create(
-                    CreateChatCompletionRequestArgs::default()
-                        .model(&self.model_name)
-                        .messages(lock.clone())
-                        .build()?,
-                )
-                .await?
-        };
+        let mut messages = self.messages.lock().await.clone();
+        if let Some(prompt) = self.answer_prompt.lock().await.as_deref() {
+            let after_system = messages
+                .iter()
+                .take_while(|message| matches!(message, ChatCompletionRequestMessage::System(_)))
+                .count();
+            messages.insert(
+                after_system,
+                ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessageArgs::default()
+                        .content(prompt)
+                        .build()
+                        .unwrap(),
+                ),
+            );
+        }
+
+        let completion = self
+            .client
+            .chat()
+            .create(
+                CreateChatCompletionRequestArgs::default()
+                    .model(&self.model_name)
+                    .messages(messages)
+                    .build()?,
+            )
+            .await?;
 
         let choice = completion
             .choices
@@ -135,9 +175,8 @@ impl BaseAgent {
 #[cfg(test)]
 mod tests {
     use crate::{
-        GetGeoLocationArgs, GetWeatherArgs,
-        agent::prompt::create_system_prompt,
-        agent::tool::{FunctionSchemaStyle, build_function_schema},
+        GetGeoLocationArgs, GetWeatherArgs, agent::prompt::create_system_prompt,
+        agent::tool::ToolDefinition,
     };
 
     use super::*;
@@ -146,26 +185,18 @@ mod tests {
     async fn test_generate_response() {
         dotenv::dotenv().unwrap();
 
-        let tools = vec![
-            build_function_schema::<GetWeatherArgs>(
+        let tools = [
+            ToolDefinition::new::<GetWeatherArgs>(
                 "get_weather",
                 "Get current weather of the location",
-                FunctionSchemaStyle::Legacy,
             ),
-            build_function_schema::<GetGeoLocationArgs>(
+            ToolDefinition::new::<GetGeoLocationArgs>(
                 "get_geo_location",
                 "Get the latitude and longitude of a city",
-                FunctionSchemaStyle::Legacy,
             ),
         ];
 
-        let system_prompt = create_system_prompt(
-            tools
-                .iter()
-                .map(|t| serde_json::to_string_pretty(t).unwrap())
-                .collect::<Vec<String>>(),
-            None,
-        );
+        let system_prompt = create_system_prompt(&tools, None).unwrap();
 
         let agent = BaseAgent::new(
             "TestAgent",
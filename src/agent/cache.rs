@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::tool::ToolFunction;
+
+/// Storage backend of cached tool results
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Value>>;
+    async fn put(&self, key: &str, value: &Value, ttl: Duration) -> Result<()>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    /// seconds since UNIX epoch
+    expires_at: u64,
+    value: Value,
+}
+
+impl CacheEntry {
+    fn is_expired(&self) -> bool {
+        now_secs() >= self.expires_at
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Cache living as long as the process
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<Value>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.is_expired() => {
+                entries.remove(key);
+                Ok(None)
+            }
+            Some(entry) => Ok(Some(entry.value.clone())),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &Value, ttl: Duration) -> Result<()> {
+        self.entries.lock().unwrap().insert(
+            key.to_string(),
+            CacheEntry {
+                key: key.to_string(),
+                expires_at: now_secs() + ttl.as_secs(),
+                value: value.clone(),
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Cache persisted as one JSON file per entry, shared across runs
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DiskCache { dir: dir.into() }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+}
+
+#[async_trait]
+impl CacheStore for DiskCache {
+    async fn get(&self, key: &str) -> Result<Option<Value>> {
+        let path = self.entry_path(key);
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(AgentError::Generic(format!(
+                    "Failed to read cache entry {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+
+        // a corrupted entry is treated as a miss and overwritten later
+        match serde_json::from_str::<CacheEntry>(&content) {
+            Ok(entry) if entry.key == key && !entry.is_expired() => Ok(Some(entry.value)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &Value, ttl: Duration) -> Result<()> {
+        let entry = CacheEntry {
+            key: key.to_string(),
+            expires_at: now_secs() + ttl.as_secs(),
+            value: value.clone(),
+        };
+        let content = serde_json::to_string(&entry)
+            .map_err(|e| AgentError::Generic(format!("Failed to serialize cache entry: {}", e)))?;
+
+        let path = self.entry_path(key);
+        let written = match tokio::fs::create_dir_all(&self.dir).await {
+            Ok(()) => tokio::fs::write(&path, content).await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| {
+            AgentError::Generic(format!(
+                "Failed to write cache entry {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Sort object keys recursively so equal arguments produce the same key
+pub fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) => {
+            let mut keys: Vec<&String> = obj.keys().collect();
+            keys.sort();
+
+            let mut sorted = Map::new();
+            for key in keys {
+                sorted.insert(key.clone(), canonicalize(&obj[key]));
+            }
+            Value::Object(sorted)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonicalize).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// A tool whose successful results are cached by tool name and arguments
+pub struct CachedTool {
+    name: String,
+    tool: Arc<dyn ToolFunction>,
+    store: Arc<dyn CacheStore>,
+    ttl: Duration,
+}
+
+impl CachedTool {
+    pub fn new<F: ToolFunction + 'static>(
+        name: &str,
+        tool: F,
+        store: Arc<dyn CacheStore>,
+        ttl: Duration,
+    ) -> Self {
+        CachedTool {
+            name: name.to_string(),
+            tool: Arc::new(tool),
+            store,
+            ttl,
+        }
+    }
+
+    pub fn cache_key(&self, args: &Value) -> String {
+        format!("{}:{}", self.name, canonicalize(args))
+    }
+}
+
+#[async_trait]
+impl ToolFunction for CachedTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let key = self.cache_key(&args);
+
+        match self.store.get(&key).await {
+            Ok(Some(value)) => {
+                eprintln!("Cache hit: {}", key.green());
+                return Ok(value);
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("{}", format!("Cache lookup failed: {}", e).red()),
+        }
+
+        let value = self.tool.call(args).await?;
+        if let Err(e) = self.store.put(&key, &value, self.ttl).await {
+            eprintln!("{}", format!("Cache store failed: {}", e).red());
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct CountingTool {
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl ToolFunction for CountingTool {
+        async fn call(&self, args: Value) -> Result<Value> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(args)
+        }
+    }
+
+    #[test]
+    fn canonical_key_ignores_order() {
+        let a = json!({"city": "London", "extra": {"b": 1, "a": 2}});
+        let b = json!({"extra": {"a": 2, "b": 1}, "city": "London"});
+        assert_eq!(canonicalize(&a).to_string(), canonicalize(&b).to_string());
+    }
+
+    #[tokio::test]
+    async fn memory_cache_hit() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let tool = CachedTool::new(
+            "echo",
+            CountingTool {
+                calls: calls.clone(),
+            },
+            Arc::new(MemoryCache::new()),
+            Duration::from_secs(60),
+        );
+
+        tool.call(json!({"city": "London"})).await.unwrap();
+        tool.call(json!({"city": "London"})).await.unwrap();
+        tool.call(json!({"city": "Paris"})).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn disk_cache_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("reactagent-cache-{}", std::process::id()));
+        let store = DiskCache::new(&dir);
+
+        store
+            .put("echo:{}", &json!({"ok": true}), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get("echo:{}").await.unwrap(),
+            Some(json!({"ok": true}))
+        );
+
+        store
+            .put("echo:expired", &json!(1), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(store.get("echo:expired").await.unwrap(), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
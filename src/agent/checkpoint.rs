@@ -0,0 +1,254 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_openai::types::ChatCompletionRequestMessage;
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::react::TranscriptEntry;
+
+/// State of a react run after its latest completed step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub run_id: String,
+    /// Interactions with the model so far
+    pub interactions: u8,
+    /// Conversation history of the underlying agent
+    pub messages: Vec<ChatCompletionRequestMessage>,
+    pub transcript: Vec<TranscriptEntry>,
+    /// Prompt to send to the model next: the user input or the latest observation
+    pub next_prompt: String,
+    /// Reply of the model whose tool calls have not been observed yet
+    pub pending_action: Option<String>,
+    /// Final answer once the run is finished
+    pub answer: Option<String>,
+    /// JSON Schema the answer of a typed run must match
+    #[serde(default)]
+    pub answer_schema: Option<Value>,
+}
+
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    async fn save(&self, checkpoint: &Checkpoint) -> Result<()>;
+    async fn load(&self, run_id: &str) -> Result<Option<Checkpoint>>;
+}
+
+/// Generate an id for a new run, unique enough for one machine
+pub fn new_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{:x}-{:x}", nanos, std::process::id())
+}
+
+fn validate_run_id(run_id: &str) -> Result<()> {
+    let valid = !run_id.is_empty()
+        && run_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AgentError::Generic(format!("Invalid run id: {}", run_id)));
+    }
+    Ok(())
+}
+
+/// Checkpoints stored as `<dir>/<run_id>.json`
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileCheckpointStore { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    async fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        validate_run_id(&checkpoint.run_id)?;
+        let content = serde_json::to_string_pretty(checkpoint)
+            .map_err(|e| AgentError::Generic(format!("Failed to serialize checkpoint: {}", e)))?;
+
+        // write to a temporary file first so a crash never leaves a truncated checkpoint
+        let path = self.dir.join(format!("{}.json", checkpoint.run_id));
+        let tmp_path = self.dir.join(format!("{}.json.tmp", checkpoint.run_id));
+        let written = match tokio::fs::create_dir_all(&self.dir).await {
+            Ok(()) => match tokio::fs::write(&tmp_path, content).await {
+                Ok(()) => tokio::fs::rename(&tmp_path, &path).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| {
+            AgentError::Generic(format!(
+                "Failed to write checkpoint {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    async fn load(&self, run_id: &str) -> Result<Option<Checkpoint>> {
+        validate_run_id(run_id)?;
+        let path = self.dir.join(format!("{}.json", run_id));
+        let content = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(AgentError::Generic(format!(
+                    "Failed to read checkpoint {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+
+        serde_json::from_str(&content).map(Some).map_err(|e| {
+            AgentError::Generic(format!("Invalid checkpoint {}: {}", path.display(), e))
+        })
+    }
+}
+
+/// Checkpoints stored in a SQLite table, one row per run
+pub struct SqliteCheckpointStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteCheckpointStore {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        Self::with_connection(Connection::open(path.into()).map_err(sqlite_error)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS checkpoints (
+                run_id TEXT PRIMARY KEY,
+                data TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            )",
+            (),
+        )
+        .map_err(sqlite_error)?;
+
+        Ok(SqliteCheckpointStore {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+}
+
+fn sqlite_error(e: rusqlite::Error) -> AgentError {
+    AgentError::Generic(format!("SQLite error: {}", e))
+}
+
+#[async_trait]
+impl CheckpointStore for SqliteCheckpointStore {
+    async fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let data = serde_json::to_string(checkpoint)
+            .map_err(|e| AgentError::Generic(format!("Failed to serialize checkpoint: {}", e)))?;
+        let run_id = checkpoint.run_id.clone();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let updated_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+            conn.lock()
+                .unwrap()
+                .execute(
+                    "INSERT INTO checkpoints (run_id, data, updated_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(run_id) DO UPDATE SET data = ?2, updated_at = ?3",
+                    (&run_id, &data, updated_at),
+                )
+                .map(|_| ())
+                .map_err(sqlite_error)
+        })
+        .await
+        .map_err(|e| AgentError::Generic(format!("Checkpoint task failed: {}", e)))?
+    }
+
+    async fn load(&self, run_id: &str) -> Result<Option<Checkpoint>> {
+        let run_id = run_id.to_string();
+        let conn = self.conn.clone();
+
+        let data = tokio::task::spawn_blocking(move || {
+            conn.lock()
+                .unwrap()
+                .query_row(
+                    "SELECT data FROM checkpoints WHERE run_id = ?1",
+                    [&run_id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(sqlite_error)
+        })
+        .await
+        .map_err(|e| AgentError::Generic(format!("Checkpoint task failed: {}", e)))??;
+
+        data.map(|data| {
+            serde_json::from_str(&data)
+                .map_err(|e| AgentError::Generic(format!("Invalid checkpoint: {}", e)))
+        })
+        .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(run_id: &str) -> Checkpoint {
+        Checkpoint {
+            run_id: run_id.to_string(),
+            interactions: 2,
+            messages: Vec::new(),
+            transcript: vec![TranscriptEntry::Prompt("hello".to_string())],
+            next_prompt: "**Observation**: sunny".to_string(),
+            pending_action: None,
+            answer: None,
+            answer_schema: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn file_store_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("reactagent-ckpt-{}", std::process::id()));
+        let store = FileCheckpointStore::new(&dir);
+
+        store.save(&checkpoint("run-1")).await.unwrap();
+        let loaded = store.load("run-1").await.unwrap().unwrap();
+        assert_eq!(loaded.interactions, 2);
+        assert_eq!(loaded.next_prompt, "**Observation**: sunny");
+
+        assert!(store.load("run-2").await.unwrap().is_none());
+        assert!(store.load("../run-1").await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sqlite_store_overwrites() {
+        let store = SqliteCheckpointStore::open_in_memory().unwrap();
+
+        store.save(&checkpoint("run-1")).await.unwrap();
+        let mut updated = checkpoint("run-1");
+        updated.answer = Some("Sunny".to_string());
+        store.save(&updated).await.unwrap();
+
+        let loaded = store.load("run-1").await.unwrap().unwrap();
+        assert_eq!(loaded.answer.as_deref(), Some("Sunny"));
+    }
+}
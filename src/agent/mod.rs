@@ -1,4 +1,9 @@
+pub mod approval;
 pub mod base;
+pub mod cache;
+pub mod checkpoint;
+pub mod policy;
 pub mod prompt;
 pub mod react;
+pub mod render;
 pub mod tool;
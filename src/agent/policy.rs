@@ -0,0 +1,295 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use colored::Colorize;
+use serde_json::Value;
+
+use super::{
+    approval::RiskLevel,
+    tool::{ToolDefinition, ToolFunction},
+};
+
+/// Execution policy of a tool, configured when the tool is added to the agent
+#[derive(Debug, Clone)]
+pub struct ToolPolicy {
+    /// Timeout of a single attempt, no timeout if None
+    pub timeout: Option<Duration>,
+    /// Retry failed attempts, only set this for idempotent tools
+    pub retry: Option<RetryPolicy>,
+    /// Mark the tool unavailable after repeated failures
+    pub circuit_breaker: Option<CircuitBreakerPolicy>,
+    /// High risk calls need the approval of the agent's approver
+    pub risk: RiskLevel,
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        ToolPolicy {
+            timeout: Some(Duration::from_secs(30)),
+            retry: None,
+            circuit_breaker: None,
+            risk: RiskLevel::Low,
+        }
+    }
+}
+
+/// A tool with its definition and the policy it is registered with
+pub type PolicyTool = (ToolDefinition, Arc<dyn ToolFunction>, ToolPolicy);
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled for every following retry
+    pub initial_backoff: Duration,
+    /// Upper bound of the backoff
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerPolicy {
+    /// Consecutive failed calls that open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a trial call is allowed
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        CircuitBreakerPolicy {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// A tool wrapped with its execution policy
+pub struct GuardedTool {
+    name: String,
+    tool: Arc<dyn ToolFunction>,
+    policy: ToolPolicy,
+    breaker: Mutex<BreakerState>,
+}
+
+impl GuardedTool {
+    pub fn new(name: &str, tool: Arc<dyn ToolFunction>, policy: ToolPolicy) -> Self {
+        GuardedTool {
+            name: name.to_string(),
+            tool,
+            policy,
+            breaker: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &ToolPolicy {
+        &self.policy
+    }
+
+    fn check_circuit(&self) -> Result<()> {
+        let Some(breaker_policy) = &self.policy.circuit_breaker else {
+            return Ok(());
+        };
+
+        let state = self.breaker.lock().unwrap();
+        if let Some(opened_at) = state.opened_at
+            && opened_at.elapsed() < breaker_policy.cooldown
+        {
+            return Err(AgentError::CircuitOpen(
+                self.name.clone(),
+                state.consecutive_failures,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn record_result(&self, success: bool) {
+        let Some(breaker_policy) = &self.policy.circuit_breaker else {
+            return;
+        };
+
+        let mut state = self.breaker.lock().unwrap();
+        if success {
+            *state = BreakerState::default();
+        } else {
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= breaker_policy.failure_threshold {
+                state.opened_at = Some(Instant::now());
+            }
+        }
+    }
+
+    async fn attempt(&self, args: Value) -> Result<Value> {
+        match self.policy.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.tool.call(args))
+                .await
+                .map_err(|_| AgentError::Timeout(self.name.clone(), timeout))?,
+            None => self.tool.call(args).await,
+        }
+    }
+}
+
+#[async_trait]
+impl ToolFunction for GuardedTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        self.check_circuit()?;
+
+        let max_retries = self.policy.retry.as_ref().map_or(0, |r| r.max_retries);
+        let mut backoff = self
+            .policy
+            .retry
+            .as_ref()
+            .map_or(Duration::ZERO, |r| r.initial_backoff);
+
+        let mut retries = 0;
+        let result = loop {
+            match self.attempt(args.clone()).await {
+                Ok(value) => break Ok(value),
+                Err(e) if retries < max_retries => {
+                    retries += 1;
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "Tool {} failed: {}, retry {}/{} in {:?}",
+                            self.name, e, retries, max_retries, backoff
+                        )
+                        .yellow()
+                    );
+                    tokio::time::sleep(backoff).await;
+
+                    if let Some(retry) = &self.policy.retry {
+                        backoff = (backoff * 2).min(retry.max_backoff);
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.record_result(result.is_ok());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct FlakyTool {
+        calls: AtomicU32,
+        failures: u32,
+    }
+
+    #[async_trait]
+    impl ToolFunction for FlakyTool {
+        async fn call(&self, _args: Value) -> Result<Value> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst);
+            if n < self.failures {
+                return Err(AgentError::Generic("flaky".to_string()));
+            }
+            Ok(Value::from(n))
+        }
+    }
+
+    struct SlowTool;
+
+    #[async_trait]
+    impl ToolFunction for SlowTool {
+        async fn call(&self, _args: Value) -> Result<Value> {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(Value::Null)
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout_slow_tool() {
+        let tool = GuardedTool::new(
+            "slow",
+            Arc::new(SlowTool),
+            ToolPolicy {
+                timeout: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+        );
+
+        let result = tool.call(Value::Null).await;
+        assert!(matches!(result, Err(AgentError::Timeout(_, _))));
+    }
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let flaky = Arc::new(FlakyTool {
+            calls: AtomicU32::new(0),
+            failures: 2,
+        });
+        let tool = GuardedTool::new(
+            "flaky",
+            flaky.clone(),
+            ToolPolicy {
+                retry: Some(RetryPolicy {
+                    max_retries: 2,
+                    initial_backoff: Duration::from_millis(1),
+                    max_backoff: Duration::from_millis(2),
+                }),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(tool.call(Value::Null).await.unwrap(), Value::from(2));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn open_circuit_after_failures() {
+        let flaky = Arc::new(FlakyTool {
+            calls: AtomicU32::new(0),
+            failures: u32::MAX,
+        });
+        let tool = GuardedTool::new(
+            "broken",
+            flaky.clone(),
+            ToolPolicy {
+                circuit_breaker: Some(CircuitBreakerPolicy {
+                    failure_threshold: 2,
+                    cooldown: Duration::from_secs(60),
+                }),
+                ..Default::default()
+            },
+        );
+
+        assert!(matches!(
+            tool.call(Value::Null).await,
+            Err(AgentError::Generic(_))
+        ));
+        assert!(matches!(
+            tool.call(Value::Null).await,
+            Err(AgentError::Generic(_))
+        ));
+        assert!(matches!(
+            tool.call(Value::Null).await,
+            Err(AgentError::CircuitOpen(_, 2))
+        ));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 2);
+    }
+}
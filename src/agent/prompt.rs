@@ -1,4 +1,12 @@
-use serde_json::Value;
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use crate::error::AgentError;
+use crate::prelude::*;
+use minijinja::Environment;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use super::{render::ToolRenderer, tool::ToolDefinition};
 
 const SYSTEM_PROMPT: &str = r#"
 You are an intelligent assistant that operates strictly in a loop of Thought → Action → PAUSE → Observation.
@@ -15,7 +23,7 @@ Repeat the loop until you have enough information to produce a final Answer.
 ***Response Format Rule***:
 You must only produce output in this strict JSON format:
 {
-  "state": "pause" | "answer",
+  "state": "pause",
   "thought": "<step-by-step reasoning>",
   "action": {
     "tool": "<tool_name>",
@@ -27,94 +35,475 @@ You must only produce output in this strict JSON format:
 
 When calling a tool, always set "state": "pause" and stop. Do not generate Observation or Answer yet.
 
-When ready to answer, use "state": "answer".
+When several tool calls do not depend on each other, replace "action" with an "actions" array to run them at once:
+{
+  "state": "pause",
+  "thought": "<step-by-step reasoning>",
+  "actions": [
+    { "id": "<unique_call_id>", "tool": "<tool_name>", "input": { } }
+  ]
+}
+The Observation is then a JSON object keyed by each call id.
+
+When ready to answer, use "state": "answer", leave out "action" and put the answer for the user in "answer":
+{
+  "state": "answer",
+  "thought": "<reasoning behind the answer>",
+  "answer": "<final answer for the user>"
+}
 
 This loop is strictly enforced. Any deviation will be considered invalid output.
 
+Write "thought" and "answer" in the language of the user's question. The JSON keys, the "state" values and the tool names always stay exactly as shown above.
+
 
 
 
 Your available tools are:
 -------------------------
-{available_tools}
+{% for tool in tools %}
+- {{ tool.name }}: {{ tool.description }}
+{{ tool.schema }}
 
+{% endfor %}
 -------------------------
 
+{% if example %}
 
 
 Example session:
 -------------------------
-{example}
+{{ example }}
 -------------------------
+{% endif %}
 
 Now it's your turn to use the tools effectively. Return only the concise final answer in a single sentence. No additional text. 
 "#;
 
-const DEFAULT_EXAMPLE: &str = r##"
+const SYSTEM_PROMPT_ZH_CN: &str = r#"
+你是一个智能助手，严格按照 Thought → Action → PAUSE → Observation 的循环工作。
+
+你必须严格遵循以下交互循环：
+
+**Thought**：针对当前任务或已有信息逐步思考。
+**Action**：从可用工具列表中选择一个工具并提供输入参数进行调用。
+**PAUSE**：立即暂停并等待结果，此后不要再输出任何内容。
+**Observation**：执行动作后你会收到一个观察结果，据此反思并继续循环。
+
+重复该循环，直到你掌握足够的信息给出最终答案。
+
+***回复格式规则***：
+你只能以如下严格的 JSON 格式输出：
 {
   "state": "pause",
-  "thought": "I need to find out the current weather conditions in London. To do this, I'll first need to get the latitude and longitude of London as these are required for the get_weather tool. Once I have the coordinates, I can use the get_weather tool to find out the current weather in London.",
+  "thought": "<逐步推理过程>",
   "action": {
-    "tool": "get_geo_location",
+    "tool": "<tool_name>",
     "input": {
-      "city": "London"
+      // 工具所需的参数
     }
   }
 }
 
+调用工具时，始终设置 "state": "pause" 并停止输出。此时不要生成 Observation 或答案。
 
-**Observation**: The latitude of London is 51.5074 and the longitude is -0.1278.
-
+当多个工具调用彼此独立时，用 "actions" 数组代替 "action"，以便同时执行：
 {
   "state": "pause",
-  "thought": "Now that I have the coordinates, I can use the get_weather tool to find out the current weather in London. I'll also specify the unit of measurement, which in this case, I'll leave as null to default to the most common unit.",
-  "action": {
-    "tool": "get_weather",
-    "input": { 
-        "city": "London", 
-        "latitude": 51.5074, 
-        "longitude": -0.1278, 
-        "unit": null 
+  "thought": "<逐步推理过程>",
+  "actions": [
+    { "id": "<unique_call_id>", "tool": "<tool_name>", "input": { } }
+  ]
+}
+此时的 Observation 是一个以各调用 id 为键的 JSON 对象。
+
+准备好回答时，使用 "state": "answer"，省略 "action"，并把给用户的答案放在 "answer" 中：
+{
+  "state": "answer",
+  "thought": "<得出答案的推理>",
+  "answer": "<给用户的最终答案>"
+}
+
+该循环会被严格执行，任何偏离都将被视为无效输出。
+
+"thought" 和 "answer" 请使用用户提问所用的语言书写。JSON 的键、"state" 的取值以及工具名称必须始终与上文完全一致，不要翻译。
+
+
+
+
+可用的工具如下：
+-------------------------
+{% for tool in tools %}
+- {{ tool.name }}: {{ tool.description }}
+{{ tool.schema }}
+
+{% endfor %}
+-------------------------
+
+{% if example %}
+
+
+示例会话：
+-------------------------
+{{ example }}
+-------------------------
+{% endif %}
+
+现在请有效地使用这些工具。只返回一句简洁的最终答案，不要附加其他内容。
+"#;
+
+/// Language of the built-in system prompts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl FromStr for Locale {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" | "en-gb" => Ok(Locale::En),
+            "zh" | "zh-cn" | "zh-hans" => Ok(Locale::ZhCn),
+            _ => Err(AgentError::Generic(format!(
+                "Unsupported language: {}, expected en or zh-CN",
+                s
+            ))),
+        }
     }
-  }
 }
 
-**Observation**: The current weather in London is described as overcast with a temperature of 12°C.
+impl Locale {
+    fn system_prompt(&self) -> &'static str {
+        match self {
+            Locale::En => SYSTEM_PROMPT,
+            Locale::ZhCn => SYSTEM_PROMPT_ZH_CN,
+        }
+    }
+
+    fn example_thought(&self, tool_name: &str, description: &str) -> String {
+        match self {
+            Locale::En => format!("I will use the {} tool to {}.", tool_name, description),
+            Locale::ZhCn => format!("我将使用 {} 工具：{}。", tool_name, description),
+        }
+    }
 
-{
-    "state": "answer",
-    "thought": "The weather in London today is overcast with a temperature of 12°C.",
-    "action": {
-        "tool": "none",
-        "input": {}
+    fn example_answer(&self) -> (&'static str, &'static str) {
+        match self {
+            Locale::En => (
+                "The observations contain everything needed to answer the question.",
+                "<final answer for the user>",
+            ),
+            Locale::ZhCn => (
+                "观察结果已包含回答问题所需的全部信息。",
+                "<给用户的最终答案>",
+            ),
+        }
+    }
+}
+
+/// A tool as presented to the model in the system prompt
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptTool {
+    pub name: String,
+    pub description: String,
+    /// Schema of the tool arguments, written by the agent's tool renderer
+    pub schema: String,
+    /// Example step calling the tool, followed by a placeholder observation
+    pub example: String,
+}
+
+/// Variables available to a prompt template
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PromptContext {
+    pub tools: Vec<PromptTool>,
+    pub example: Option<String>,
+    /// Extra variables used by custom templates
+    #[serde(flatten)]
+    pub variables: HashMap<String, Value>,
+}
+
+/// A Jinja-style system prompt template, e.g. `{{ name }}`, `{% if ... %}`,
+/// `{% for tool in tools %}`. Variable values are inserted verbatim and never
+/// interpreted as template syntax; literal `{{` can be written in `{% raw %}` blocks.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    source: String,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        PromptTemplate::new(SYSTEM_PROMPT)
+    }
+}
+
+impl PromptTemplate {
+    /// The built-in system prompt in the given language
+    pub fn for_locale(locale: Locale) -> Self {
+        PromptTemplate::new(locale.system_prompt())
+    }
+
+    pub fn new(source: impl Into<String>) -> Self {
+        PromptTemplate {
+            source: source.into(),
+        }
+    }
+
+    /// Load a template from a file, checking its syntax
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            AgentError::Generic(format!(
+                "Failed to read prompt template {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        environment().template_from_str(&source).map_err(|e| {
+            AgentError::Generic(format!("Invalid prompt template {}: {}", path.display(), e))
+        })?;
+
+        Ok(PromptTemplate::new(source))
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn render(&self, context: &PromptContext) -> Result<String> {
+        environment()
+            .render_str(&self.source, context)
+            .map_err(|e| AgentError::Generic(format!("Failed to render prompt template: {}", e)))
     }
 }
-"##;
 
-pub fn create_system_prompt(tool_schema_list: Vec<String>, example: Option<String>) -> String {
-    let mut avaiable_tools = String::new();
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env
+}
+
+fn prompt_tools(
+    tools: &[ToolDefinition],
+    renderer: ToolRenderer,
+    locale: Locale,
+) -> Vec<PromptTool> {
+    tools
+        .iter()
+        .map(|tool| PromptTool {
+            name: tool.name.clone(),
+            description: tool.description.clone(),
+            schema: renderer.render(tool),
+            example: example_step(tool, locale),
+        })
+        .collect()
+}
+
+/// Example input of a tool: the example attached to the tool, the first example of its
+/// schemars metadata, or sample values of its required parameters.
+fn example_input(tool: &ToolDefinition) -> Value {
+    if let Some(example) = tool.example.as_ref().filter(|e| e.is_object()) {
+        return example.clone();
+    }
+
+    let parameters = &tool.parameters;
+    if let Some(example) = parameters.get("examples").and_then(|e| e.get(0)) {
+        return example.clone();
+    }
+
+    let required: Vec<&str> = parameters
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
 
-    for tool_json in tool_schema_list {
-        let parsed: Value = serde_json::from_str(&tool_json).expect("Invalid JSON");
-        if let Some(name) = parsed.get("name").and_then(|v| v.as_str()) {
-            if let Some(desc) = parsed.get("description").and_then(|v| v.as_str()) {
-                avaiable_tools.push_str(&format!("- {}: {}\n", name, desc));
-                avaiable_tools.push_str(&tool_json);
-                avaiable_tools.push_str("\n\n");
+    let mut input = Map::new();
+    if let Some(properties) = parameters.get("properties").and_then(Value::as_object) {
+        for (name, property) in properties {
+            if required.contains(&name.as_str()) || property.get("examples").is_some() {
+                input.insert(name.clone(), sample_value(name, property));
             }
         }
     }
 
-    let example_prompt = example.unwrap_or(DEFAULT_EXAMPLE.to_string());
+    Value::Object(input)
+}
+
+fn sample_value(name: &str, property: &Value) -> Value {
+    if let Some(example) = property.get("examples").and_then(|e| e.get(0)) {
+        return example.clone();
+    }
+    if let Some(default) = property.get("default").filter(|d| !d.is_null()) {
+        return default.clone();
+    }
+    if let Some(variant) = property.get("enum").and_then(|e| e.get(0)) {
+        return variant.clone();
+    }
+
+    let ty = match property.get("type") {
+        Some(Value::String(ty)) => ty.as_str(),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|ty| *ty != "null")
+            .unwrap_or("null"),
+        _ => "string",
+    };
+
+    match ty {
+        "number" => json!(0.0),
+        "integer" => json!(0),
+        "boolean" => json!(true),
+        "array" => json!([]),
+        "object" => json!({}),
+        "null" => Value::Null,
+        _ => json!(format!("<{}>", name)),
+    }
+}
+
+/// Example step of a tool, a string attached as example to the tool is used verbatim
+fn example_step(tool: &ToolDefinition, locale: Locale) -> String {
+    if let Some(Value::String(snippet)) = &tool.example {
+        return snippet.clone();
+    }
+
+    let mut chars = tool.description.chars();
+    let description = match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    };
+
+    format!(
+        r#"{{
+  "state": "pause",
+  "thought": {},
+  "action": {{
+    "tool": {},
+    "input": {}
+  }}
+}}
+
+**Observation**: <result of {}>"#,
+        json!(locale.example_thought(&tool.name, &description)),
+        json!(tool.name),
+        example_input(tool),
+        tool.name
+    )
+}
+
+/// Example session calling every tool once, then answering
+pub fn generate_example(tools: &[PromptTool], locale: Locale) -> Option<String> {
+    if tools.is_empty() {
+        return None;
+    }
+
+    let mut example = tools
+        .iter()
+        .map(|tool| tool.example.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n\n");
+    let (thought, answer) = locale.example_answer();
+    example.push_str(&format!(
+        r#"
+
+{{
+  "state": "answer",
+  "thought": {},
+  "answer": {}
+}}"#,
+        json!(thought),
+        json!(answer)
+    ));
+
+    Some(example)
+}
+
+/// Settings of the system prompt of an agent
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    /// Custom template, the built-in prompt of `locale` if None
+    pub template: Option<PromptTemplate>,
+    pub renderer: ToolRenderer,
+    /// Language of the built-in prompt and the generated example session
+    pub locale: Locale,
+    /// Example session, generated from the tools if None
+    pub example: Option<String>,
+}
+
+pub fn create_system_prompt(tools: &[ToolDefinition], example: Option<String>) -> Result<String> {
+    create_system_prompt_with_options(
+        tools,
+        &PromptOptions {
+            example,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn create_system_prompt_with_template(
+    template: &PromptTemplate,
+    tools: &[ToolDefinition],
+    example: Option<String>,
+) -> Result<String> {
+    create_system_prompt_with_options(
+        tools,
+        &PromptOptions {
+            template: Some(template.clone()),
+            example,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn create_system_prompt_with_options(
+    tools: &[ToolDefinition],
+    options: &PromptOptions,
+) -> Result<String> {
+    let tools = prompt_tools(tools, options.renderer, options.locale);
+    let example = options
+        .example
+        .clone()
+        .or_else(|| generate_example(&tools, options.locale));
 
-    SYSTEM_PROMPT
-        .replace("{available_tools}", &avaiable_tools)
-        .replace("{example}", &example_prompt)
+    let template = match &options.template {
+        Some(template) => template.clone(),
+        None => PromptTemplate::for_locale(options.locale),
+    };
+    template.render(&PromptContext {
+        tools,
+        example,
+        variables: HashMap::new(),
+    })
+}
+
+const ANSWER_SCHEMA_PROMPT: &str = r#"
+Instead of a single sentence, the "answer" field of your final answer must be JSON matching exactly this JSON Schema:
+{{ answer_schema }}
+"#;
+
+pub fn create_answer_schema_prompt(answer_schema: &Value) -> String {
+    let context = HashMap::from([(
+        "answer_schema",
+        serde_json::to_string_pretty(answer_schema).unwrap(),
+    )]);
+
+    environment()
+        .render_str(ANSWER_SCHEMA_PROMPT, context)
+        .expect("The answer schema template must render")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agent::tool::attach_example;
+
+    fn weather_tool() -> ToolDefinition {
+        ToolDefinition::from_schema(&serde_json::from_str(SCHEMA_STR).unwrap()).unwrap()
+    }
 
     const SCHEMA_STR: &str = r###"
         {
@@ -156,7 +545,103 @@ mod tests {
 
     #[test]
     fn should_work() {
-        let prompt = create_system_prompt(vec![SCHEMA_STR.to_owned()], None);
+        let prompt = create_system_prompt(&[weather_tool()], None).unwrap();
         println!("{prompt}");
     }
+
+    #[test]
+    fn keep_placeholders_in_tool_descriptions() {
+        let mut tool = weather_tool();
+        tool.description = "Get weather, see {example}".to_string();
+        let prompt = create_system_prompt(&[tool], Some("EXAMPLE".to_string())).unwrap();
+
+        assert!(prompt.contains("- get_weather: Get weather, see {example}"));
+        assert_eq!(prompt.matches("EXAMPLE").count(), 1);
+    }
+
+    #[test]
+    fn render_custom_template() {
+        let template = PromptTemplate::new(
+            "{% for tool in tools %}{{ tool.name }};{% endfor %}\
+             {% if example %}with example{% else %}no example{% endif %} for {{ team }}",
+        );
+        let context = PromptContext {
+            tools: prompt_tools(&[weather_tool()], ToolRenderer::default(), Locale::En),
+            example: None,
+            variables: HashMap::from([("team".to_string(), Value::from("ops"))]),
+        };
+
+        assert_eq!(
+            template.render(&context).unwrap(),
+            "get_weather;no example for ops"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_template_file() {
+        let path = std::env::temp_dir().join(format!("reactagent-{}.j2", std::process::id()));
+        std::fs::write(&path, "{% for tool in tools %}").unwrap();
+
+        assert!(PromptTemplate::from_file(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn generate_example_from_schema() {
+        let tools = prompt_tools(&[weather_tool()], ToolRenderer::default(), Locale::En);
+        let example = generate_example(&tools, Locale::En).unwrap();
+
+        assert!(example.contains(r#""tool": "get_weather""#));
+        assert!(example.contains(r#""city":"<city>""#));
+        assert!(example.contains(r#""latitude":0.0"#));
+        assert!(!example.contains("unit"));
+        assert!(!example.contains("London"));
+        assert!(example.contains(r#""state": "answer""#));
+    }
+
+    #[test]
+    fn use_attached_examples() {
+        let schema: Value = serde_json::from_str(SCHEMA_STR).unwrap();
+        let with_input = attach_example(
+            schema.clone(),
+            json!({"city": "Oslo", "latitude": 59.91, "longitude": 10.75}),
+        );
+        let tools = prompt_tools(
+            &[ToolDefinition::from_schema(&with_input).unwrap()],
+            ToolRenderer::default(),
+            Locale::En,
+        );
+        assert!(tools[0].example.contains(r#""city":"Oslo""#));
+
+        let with_snippet = attach_example(schema, json!("custom get_weather snippet"));
+        let tools = prompt_tools(
+            &[ToolDefinition::from_schema(&with_snippet).unwrap()],
+            ToolRenderer::default(),
+            Locale::En,
+        );
+        assert_eq!(tools[0].example, "custom get_weather snippet");
+    }
+
+    #[test]
+    fn localized_prompt() {
+        assert_eq!("zh_CN".parse::<Locale>().unwrap(), Locale::ZhCn);
+        assert!("fr".parse::<Locale>().is_err());
+
+        let options = PromptOptions {
+            locale: Locale::ZhCn,
+            ..Default::default()
+        };
+        let prompt = create_system_prompt_with_options(&[weather_tool()], &options).unwrap();
+
+        assert!(prompt.contains("可用的工具如下"));
+        assert!(prompt.contains("我将使用 get_weather 工具"));
+        // the protocol keys are never translated
+        for key in [
+            r#""state": "pause""#,
+            r#""actions""#,
+            r#""state": "answer""#,
+        ] {
+            assert!(prompt.contains(key));
+        }
+    }
 }
@@ -3,10 +3,20 @@ use std::{collections::HashMap, sync::Arc};
 use crate::error::AgentError;
 use crate::prelude::*;
 use colored::Colorize;
-use serde::Deserialize;
-use serde_json::Value;
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value, json};
+use tokio::{sync::Semaphore, task::JoinSet};
+use tokio_util::sync::CancellationToken;
 
-use super::{base::Agent, tool::ToolFunction};
+use super::{
+    approval::{ApprovalDecision, Approver, RiskLevel},
+    base::Agent,
+    checkpoint::{Checkpoint, CheckpointStore, new_run_id},
+    policy::{GuardedTool, ToolPolicy},
+    prompt::create_answer_schema_prompt,
+    tool::ToolFunction,
+};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -19,21 +29,98 @@ pub enum ReactState {
 pub struct ActionCall {
     pub state: ReactState,
     pub thought: String,
-    pub action: Action,
+    #[serde(default)]
+    pub action: Option<Action>,
+    /// Independent tool calls executed concurrently in a single step
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    /// User-facing final answer, a string or the structured answer of a typed run
+    #[serde(default)]
+    pub answer: Option<Value>,
+}
+
+impl ActionCall {
+    /// All tool calls requested in this step, placeholder "none" calls excluded
+    pub fn tool_calls(&self) -> Vec<&Action> {
+        self.action
+            .iter()
+            .chain(self.actions.iter())
+            .filter(|action| action.tool != "none")
+            .collect()
+    }
+
+    /// The user-facing answer, falling back to `thought` for replies in the old shape
+    /// where the answer was written there next to a dummy "none" action.
+    pub fn final_answer(&self) -> String {
+        match &self.answer {
+            Some(Value::String(answer)) => answer.clone(),
+            Some(answer) if !answer.is_null() => answer.to_string(),
+            _ => self.thought.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Action {
+    /// Call id used as the key of the combined observation
+    #[serde(default)]
+    pub id: Option<String>,
     pub tool: String,
     pub input: serde_json::Value,
 }
 
+/// A message exchanged with the model during a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TranscriptEntry {
+    /// User input or observation sent to the model
+    Prompt(String),
+    /// Raw reply of the model
+    Response(String),
+}
+
+/// Check of the structured answer of a typed run, returning why it is rejected
+type AnswerValidator = fn(&Value) -> core::result::Result<(), String>;
+
+fn validate_answer<A: DeserializeOwned>(answer: &Value) -> core::result::Result<(), String> {
+    serde_json::from_value::<A>(answer.clone())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn answer_schema<A: JsonSchema>() -> Result<Value> {
+    serde_json::to_value(schema_for!(A))
+        .map_err(|e| AgentError::Generic(format!("Invalid answer schema: {}", e)))
+}
+
+fn typed_answer<A: DeserializeOwned>(outcome: ReactOutcome) -> Result<A> {
+    match outcome {
+        ReactOutcome::Answer(answer) => serde_json::from_str(&answer)
+            .map_err(|e| AgentError::Generic(format!("Invalid answer: {}", e))),
+        ReactOutcome::Cancelled(_) => Err(AgentError::Generic("Run cancelled".to_string())),
+    }
+}
+
+/// How a react run ended
+#[derive(Debug)]
+pub enum ReactOutcome {
+    Answer(String),
+    /// The run was cancelled, with the transcript up to that point
+    Cancelled(Vec<TranscriptEntry>),
+}
+
 pub struct ReactAgent<T: Agent> {
     pub name: String,
     pub description: String,
     max_interactions: u8,
+    max_parallel_tools: usize,
     agent: T,
-    tools: HashMap<String, Arc<dyn ToolFunction>>,
+    tools: HashMap<String, Arc<GuardedTool>>,
+    approver: Option<Arc<dyn Approver>>,
+    transcript: Vec<TranscriptEntry>,
+    checkpoint_store: Option<Arc<dyn CheckpointStore>>,
+    run_id: Option<String>,
+    /// Schema of the answer of the current typed run
+    answer_schema: Option<Value>,
 }
 
 impl<T: Agent> ReactAgent<T> {
@@ -43,7 +130,13 @@ impl<T: Agent> ReactAgent<T> {
             description,
             agent,
             max_interactions: max_interactions.unwrap_or(10), // Default to 10 if not specified
+            max_parallel_tools: 4,
             tools: HashMap::new(),
+            approver: None,
+            transcript: Vec::new(),
+            checkpoint_store: None,
+            run_id: None,
+            answer_schema: None,
         }
     }
 
@@ -55,38 +148,286 @@ impl<T: Agent> ReactAgent<T> {
         &self.description
     }
 
+    /// Limit of tool calls running at the same time when several actions are requested in one step
+    pub fn set_max_parallel_tools(&mut self, limit: usize) {
+        self.max_parallel_tools = limit.max(1);
+    }
+
+    /// Transcript of the latest run
+    pub fn transcript(&self) -> &[TranscriptEntry] {
+        &self.transcript
+    }
+
+    /// Persist a checkpoint after every step so that runs can be resumed
+    pub fn set_checkpoint_store<S: CheckpointStore + 'static>(&mut self, store: S) {
+        self.checkpoint_store = Some(Arc::new(store));
+    }
+
+    /// Id of the latest run, only set when a checkpoint store is configured
+    pub fn run_id(&self) -> Option<&str> {
+        self.run_id.as_deref()
+    }
+
     pub fn add_tool<F: ToolFunction + 'static>(&mut self, name: &str, tool: F) {
-        self.tools.insert(name.to_string(), Arc::new(tool));
+        self.add_tool_with_policy(name, tool, ToolPolicy::default());
+    }
+
+    pub fn add_tool_with_policy<F: ToolFunction + 'static>(
+        &mut self,
+        name: &str,
+        tool: F,
+        policy: ToolPolicy,
+    ) {
+        self.tools.insert(
+            name.to_string(),
+            Arc::new(GuardedTool::new(name, Arc::new(tool), policy)),
+        );
     }
 
     pub fn get_tool(&self, name: &str) -> Option<Arc<dyn ToolFunction>> {
-        self.tools.get(name).cloned()
+        self.tools
+            .get(name)
+            .map(|tool| tool.clone() as Arc<dyn ToolFunction>)
+    }
+
+    /// Route calls of high risk tools to the approver before they are executed
+    pub fn set_approver<A: Approver + 'static>(&mut self, approver: A) {
+        self.approver = Some(Arc::new(approver));
+    }
+
+    /// Ask for approval of a high risk call, returning the arguments to run it with
+    /// or the reason of the rejection. Without an approver high risk calls are rejected.
+    async fn review_call(
+        &self,
+        tool_name: &str,
+        tool_args: &Value,
+    ) -> core::result::Result<Value, String> {
+        let high_risk = self
+            .tools
+            .get(tool_name)
+            .is_some_and(|tool| tool.policy().risk >= RiskLevel::High);
+        if !high_risk {
+            return Ok(tool_args.clone());
+        }
+
+        let Some(approver) = self.approver.as_ref() else {
+            return Err(format!(
+                "{} needs approval and no approver is configured",
+                tool_name
+            ));
+        };
+
+        match approver.review(tool_name, tool_args).await {
+            ApprovalDecision::Approve => Ok(tool_args.clone()),
+            ApprovalDecision::Edit(edited_args) => {
+                println!(
+                    "Arguments of {} edited to: {}",
+                    tool_name.italic(),
+                    edited_args.to_string().italic()
+                );
+                Ok(edited_args)
+            }
+            ApprovalDecision::Reject(reason) => Err(reason),
+        }
     }
 
     pub async fn react_loop(&mut self, user_input: &str) -> Result<String> {
-        let mut interactions = 0_u8;
+        match self
+            .react_loop_with_cancel(user_input, CancellationToken::new())
+            .await?
+        {
+            ReactOutcome::Answer(answer) => Ok(answer),
+            ReactOutcome::Cancelled(_) => Err(AgentError::Generic("Run cancelled".to_string())),
+        }
+    }
+
+    /// Run the react loop until an answer is found or `cancel` is triggered, which stops
+    /// the run between steps or aborts the in-flight LLM or tool call.
+    pub async fn react_loop_with_cancel(
+        &mut self,
+        user_input: &str,
+        cancel: CancellationToken,
+    ) -> Result<ReactOutcome> {
+        self.start_run(None).await;
+        self.run(0, user_input.to_string(), None, None, cancel)
+            .await
+    }
+
+    /// Reset the state of the previous run, `answer_schema` is set for typed runs
+    async fn start_run(&mut self, answer_schema: Option<Value>) {
+        self.transcript.clear();
+        self.run_id = self.checkpoint_store.as_ref().map(|_| new_run_id());
+        if let Some(run_id) = &self.run_id {
+            println!("Run id: {}", run_id.yellow());
+        }
+        self.set_answer_schema(answer_schema).await;
+    }
+
+    async fn set_answer_schema(&mut self, answer_schema: Option<Value>) {
+        let prompt = answer_schema.as_ref().map(create_answer_schema_prompt);
+        self.agent.set_answer_prompt(prompt.as_deref()).await;
+        self.answer_schema = answer_schema;
+    }
+
+    /// Run the react loop with the final answer required to match the JSON Schema of `A`.
+    /// The model is asked again whenever its answer cannot be deserialized into `A`.
+    pub async fn react_loop_typed<A: JsonSchema + DeserializeOwned>(
+        &mut self,
+        user_input: &str,
+    ) -> Result<A> {
+        self.start_run(Some(answer_schema::<A>()?)).await;
+        let outcome = self
+            .run(
+                0,
+                user_input.to_string(),
+                None,
+                Some(validate_answer::<A>),
+                CancellationToken::new(),
+            )
+            .await?;
+
+        typed_answer(outcome)
+    }
+
+    pub async fn resume(&mut self, run_id: &str) -> Result<String> {
+        match self
+            .resume_with_cancel(run_id, CancellationToken::new())
+            .await?
+        {
+            ReactOutcome::Answer(answer) => Ok(answer),
+            ReactOutcome::Cancelled(_) => Err(AgentError::Generic("Run cancelled".to_string())),
+        }
+    }
+
+    /// Continue a checkpointed run from its last completed step
+    pub async fn resume_with_cancel(
+        &mut self,
+        run_id: &str,
+        cancel: CancellationToken,
+    ) -> Result<ReactOutcome> {
+        self.resume_run(run_id, None, None, cancel).await
+    }
 
-        let mut next_prompt = user_input.to_string();
+    /// Continue a checkpointed run of [`Self::react_loop_typed`], validating its answer again
+    pub async fn resume_typed<A: JsonSchema + DeserializeOwned>(
+        &mut self,
+        run_id: &str,
+    ) -> Result<A> {
+        let outcome = self
+            .resume_run(
+                run_id,
+                Some(answer_schema::<A>()?),
+                Some(validate_answer::<A>),
+                CancellationToken::new(),
+            )
+            .await?;
+        typed_answer(outcome)
+    }
+
+    async fn resume_run(
+        &mut self,
+        run_id: &str,
+        answer_schema: Option<Value>,
+        answer_validator: Option<AnswerValidator>,
+        cancel: CancellationToken,
+    ) -> Result<ReactOutcome> {
+        let store = self
+            .checkpoint_store
+            .clone()
+            .ok_or_else(|| AgentError::Generic("No checkpoint store configured".to_string()))?;
+        let checkpoint = store.load(run_id).await?.ok_or_else(|| {
+            AgentError::Generic(format!("No checkpoint found for run {}", run_id))
+        })?;
+
+        // a typed run resumed untyped would skip the validation of its answer
+        if checkpoint.answer_schema != answer_schema {
+            return Err(AgentError::Generic(match checkpoint.answer_schema {
+                Some(_) if answer_schema.is_none() => format!(
+                    "Run {} expects a typed answer, resume it with resume_typed",
+                    run_id
+                ),
+                _ => format!("Run {} was started with another answer type", run_id),
+            }));
+        }
+        if let Some(answer) = checkpoint.answer {
+            return Ok(ReactOutcome::Answer(answer));
+        }
+
+        println!(
+            "Resuming run {} after {} interactions",
+            run_id.yellow(),
+            checkpoint.interactions
+        );
+        self.agent.restore_history(checkpoint.messages).await;
+        self.transcript = checkpoint.transcript;
+        self.run_id = Some(run_id.to_string());
+        self.set_answer_schema(answer_schema).await;
+
+        self.run(
+            checkpoint.interactions,
+            checkpoint.next_prompt,
+            checkpoint.pending_action,
+            answer_validator,
+            cancel,
+        )
+        .await
+    }
+
+    async fn run(
+        &mut self,
+        mut interactions: u8,
+        mut next_prompt: String,
+        mut pending_action: Option<String>,
+        answer_validator: Option<AnswerValidator>,
+        cancel: CancellationToken,
+    ) -> Result<ReactOutcome> {
         loop {
-            println!("\nprompt: {}\n", next_prompt.blue());
-
-            interactions += 1;
-            if interactions > self.max_interactions {
-                return Err(AgentError::Generic(format!(
-                    "Maximum interactions {} reached",
-                    self.max_interactions
-                )));
+            if cancel.is_cancelled() {
+                return Ok(self.cancelled());
             }
 
-            let response = self.agent.step(&next_prompt).await;
-            let json_resp = match response {
-                Ok(res) => res,
-                Err(e) => {
-                    println!(
-                        "{}",
-                        format!("Failed to get response from agent: {}", e).red()
-                    );
-                    continue;
+            // a resumed run first observes the tool calls it was interrupted in
+            let json_resp = match pending_action.take() {
+                Some(json_resp) => json_resp,
+                None => {
+                    println!("\nprompt: {}\n", next_prompt.blue());
+
+                    interactions += 1;
+                    if interactions > self.max_interactions {
+                        return Err(AgentError::Generic(format!(
+                            "Maximum interactions {} reached",
+                            self.max_interactions
+                        )));
+                    }
+
+                    self.transcript
+                        .push(TranscriptEntry::Prompt(next_prompt.clone()));
+                    // a step without a reply must not leave its prompt in the history
+                    let history = self.agent.history().await;
+                    let response = tokio::select! {
+                        _ = cancel.cancelled() => {
+                            self.agent.restore_history(history).await;
+                            return Ok(self.cancelled());
+                        }
+                        response = self.agent.step(&next_prompt) => response,
+                    };
+                    let json_resp = match response {
+                        Ok(res) => res,
+                        Err(e) => {
+                            println!(
+                                "{}",
+                                format!("Failed to get response from agent: {}", e).red()
+                            );
+                            self.agent.restore_history(history).await;
+                            continue;
+                        }
+                    };
+
+                    self.transcript
+                        .push(TranscriptEntry::Response(json_resp.clone()));
+                    self.save_checkpoint(interactions, &next_prompt, Some(&json_resp), None)
+                        .await;
+                    json_resp
                 }
             };
 
@@ -97,28 +438,46 @@ impl<T: Agent> ReactAgent<T> {
                     }
                     ReactState::ANSWER => {
                         println!("Answer: {}", json_resp.cyan());
+
+                        let answer = match answer_validator {
+                            Some(validate) => {
+                                let payload = parsed_resp.answer.clone().unwrap_or(Value::Null);
+                                if let Err(e) = validate(&payload) {
+                                    println!(
+                                        "{}",
+                                        format!("Answer does not match the schema: {}", e).red()
+                                    );
+                                    next_prompt = format!(
+                                        "**Observation**: The \"answer\" does not match the required JSON Schema: {}. Reply again with a valid \"answer\".",
+                                        e
+                                    );
+                                    self.save_checkpoint(interactions, &next_prompt, None, None)
+                                        .await;
+                                    continue;
+                                }
+                                payload.to_string()
+                            }
+                            None => parsed_resp.final_answer(),
+                        };
+
                         println!("Interaction {} times.", interactions.to_string().yellow());
-                        return Ok(parsed_resp.thought.clone());
+                        self.save_checkpoint(interactions, &next_prompt, None, Some(&answer))
+                            .await;
+                        return Ok(ReactOutcome::Answer(answer));
                     }
                 }
 
-                let observation: String;
-
-                // Process the action call
-                if parsed_resp.action.tool != "none" {
-                    // Execute the tool with the provided arguments
-                    let tool_name = &parsed_resp.action.tool;
-                    let tool_args = &parsed_resp.action.input;
-
-                    // call the actual tool with its name and arguments
-                    let tool_result = self.execute_tool(tool_name, tool_args).await?;
-                    observation = tool_result;
-                } else {
-                    observation = parsed_resp.thought;
-                }
+                // Process the action calls
+                let tool_calls = parsed_resp.tool_calls();
+                let observation = tokio::select! {
+                    _ = cancel.cancelled() => return Ok(self.cancelled()),
+                    observation = self.observe(&parsed_resp.thought, &tool_calls) => observation,
+                };
 
                 println!("Observation: {}", observation.cyan());
                 next_prompt = format!("**Observation**: {}", observation);
+                self.save_checkpoint(interactions, &next_prompt, None, None)
+                    .await;
             } else {
                 println!(
                     "Failed to parse action call JSON: <BEGIN>\n{}\n<END>",
@@ -129,7 +488,116 @@ impl<T: Agent> ReactAgent<T> {
         }
     }
 
-    pub async fn execute_tool(&mut self, tool_name: &str, tool_args: &Value) -> Result<String> {
+    async fn save_checkpoint(
+        &self,
+        interactions: u8,
+        next_prompt: &str,
+        pending_action: Option<&str>,
+        answer: Option<&str>,
+    ) {
+        let (Some(store), Some(run_id)) = (&self.checkpoint_store, &self.run_id) else {
+            return;
+        };
+
+        let checkpoint = Checkpoint {
+            run_id: run_id.clone(),
+            interactions,
+            messages: self.agent.history().await,
+            transcript: self.transcript.clone(),
+            next_prompt: next_prompt.to_string(),
+            pending_action: pending_action.map(str::to_string),
+            answer: answer.map(str::to_string),
+            answer_schema: self.answer_schema.clone(),
+        };
+
+        // a failed checkpoint only costs resumability, the run itself goes on
+        if let Err(e) = store.save(&checkpoint).await {
+            println!("{}", format!("Failed to save checkpoint: {}", e).red());
+        }
+    }
+
+    fn cancelled(&self) -> ReactOutcome {
+        println!("{}", "Run cancelled".yellow());
+        ReactOutcome::Cancelled(self.transcript.clone())
+    }
+
+    /// Execute the tool calls of a step, or echo the thought if there is none
+    async fn observe(&self, thought: &str, tool_calls: &[&Action]) -> String {
+        match tool_calls {
+            [] => thought.to_string(),
+            [action] => {
+                // Execute the tool with the provided arguments
+                let tool_name = &action.tool;
+                let tool_args = &action.input;
+
+                // call the actual tool with its name and arguments, failures are
+                // reported to the model so it can choose another way
+                match self.execute_tool(tool_name, tool_args).await {
+                    Ok(tool_result) => tool_result,
+                    Err(e) => format!("Tool {} failed: {}", tool_name, e),
+                }
+            }
+            actions => self.execute_tools(actions).await,
+        }
+    }
+
+    /// Execute independent tool calls concurrently, returning one observation keyed by call id
+    pub async fn execute_tools(&self, actions: &[&Action]) -> String {
+        let semaphore = Arc::new(Semaphore::new(self.max_parallel_tools));
+        let mut join_set = JoinSet::new();
+
+        let mut observation = Map::new();
+
+        for (action, id) in actions.iter().zip(unique_call_ids(actions)) {
+            let tool_name = action.tool.clone();
+
+            // approvals are asked one by one before anything runs
+            let tool_args = match self.review_call(&tool_name, &action.input).await {
+                Ok(tool_args) => tool_args,
+                Err(reason) => {
+                    observation.insert(
+                        id,
+                        json!({ "tool": tool_name, "error": format!("Tool call rejected: {}", reason) }),
+                    );
+                    continue;
+                }
+            };
+            let tool = self.get_tool(&tool_name);
+            let semaphore = semaphore.clone();
+
+            join_set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                println!(
+                    "\nExecuted tool: {} ({}) with args: {}",
+                    tool_name.italic(),
+                    id,
+                    tool_args.to_string().italic()
+                );
+
+                let output = match tool {
+                    Some(tool) => match tool.call(tool_args).await {
+                        Ok(value) => json!({ "tool": tool_name, "output": value }),
+                        Err(e) => json!({ "tool": tool_name, "error": e.to_string() }),
+                    },
+                    None => json!({ "tool": tool_name, "error": format!("Not found tool: {}", tool_name) }),
+                };
+                (id, output)
+            });
+        }
+
+        while let Some(joined) = join_set.join_next().await {
+            match joined {
+                Ok((id, output)) => {
+                    observation.insert(id, output);
+                }
+                Err(e) => println!("{}", format!("Tool task failed: {}", e).red()),
+            }
+        }
+
+        Value::Object(observation).to_string()
+    }
+
+    pub async fn execute_tool(&self, tool_name: &str, tool_args: &Value) -> Result<String> {
         println!(
             "\nExecuted tool: {} with args: {}",
             tool_name.italic(),
@@ -137,7 +605,12 @@ impl<T: Agent> ReactAgent<T> {
         );
 
         if let Some(tool) = self.get_tool(tool_name) {
-            return Ok(tool.call(tool_args.clone()).await?.to_string());
+            let tool_args = match self.review_call(tool_name, tool_args).await {
+                Ok(tool_args) => tool_args,
+                Err(reason) => return Ok(format!("Tool call rejected: {}", reason)),
+            };
+
+            return Ok(tool.call(tool_args).await?.to_string());
         }
 
         Err(AgentError::Generic(format!(
@@ -147,9 +620,30 @@ impl<T: Agent> ReactAgent<T> {
     }
 }
 
+/// Keys of the calls in the combined observation: the id given by the model or `call_<n>`,
+/// with a `_<n>` suffix added to repeated ids so no observation overwrites another
+fn unique_call_ids(actions: &[&Action]) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::with_capacity(actions.len());
+    for (index, action) in actions.iter().enumerate() {
+        let id = action
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("call_{}", index + 1));
+        let mut unique = id.clone();
+        let mut suffix = 2;
+        while ids.contains(&unique) {
+            unique = format!("{}_{}", id, suffix);
+            suffix += 1;
+        }
+        ids.push(unique);
+    }
+    ids
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_openai::types::ChatCompletionRequestMessage;
 
     #[test]
     fn parse_action_call() {
@@ -168,7 +662,407 @@ mod tests {
 
         if let Ok(action_call) = serde_json::from_str::<ActionCall>(raw_resp) {
             assert!(matches!(action_call.state, ReactState::PAUSE));
-            assert_eq!(action_call.action.tool, "get_geo_location".to_string());
+            assert_eq!(
+                action_call.tool_calls()[0].tool,
+                "get_geo_location".to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_answer() {
+        let raw_resp = r#"
+        {
+            "state": "answer",
+            "thought": "The observation gives the temperature in Celsius.",
+            "answer": "It is 12°C and overcast in London."
+        }
+        "#;
+
+        let action_call = serde_json::from_str::<ActionCall>(raw_resp).unwrap();
+        assert!(matches!(action_call.state, ReactState::ANSWER));
+        assert!(action_call.tool_calls().is_empty());
+        assert_eq!(
+            action_call.final_answer(),
+            "It is 12°C and overcast in London."
+        );
+    }
+
+    #[test]
+    fn parse_legacy_answer() {
+        let raw_resp = r#"
+        {
+            "state": "answer",
+            "thought": "It is 12°C and overcast in London.",
+            "action": { "tool": "none", "input": {} }
         }
+        "#;
+
+        let action_call = serde_json::from_str::<ActionCall>(raw_resp).unwrap();
+        assert!(action_call.tool_calls().is_empty());
+        assert_eq!(
+            action_call.final_answer(),
+            "It is 12°C and overcast in London."
+        );
+    }
+
+    #[test]
+    fn parse_parallel_actions() {
+        let raw_resp = r#"
+        {
+            "state": "pause",
+            "thought": "I need the coordinates of both cities.",
+            "actions": [
+                { "id": "paris", "tool": "get_geo_location", "input": { "city": "Paris" } },
+                { "id": "rome", "tool": "get_geo_location", "input": { "city": "Rome" } }
+            ]
+        }
+        "#;
+
+        let action_call = serde_json::from_str::<ActionCall>(raw_resp).unwrap();
+        let calls = action_call.tool_calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].id.as_deref(), Some("rome"));
+    }
+
+    struct IdleAgent;
+
+    #[async_trait::async_trait]
+    impl Agent for IdleAgent {
+        fn name(&self) -> &str {
+            "idle"
+        }
+
+        fn description(&self) -> &str {
+            "never called"
+        }
+
+        async fn step<'a>(&self, _message: &'a str) -> Result<String> {
+            Ok(r#"{"state": "answer", "thought": "idle", "answer": "idle"}"#.to_string())
+        }
+    }
+
+    /// Returns once the other calls sharing its barrier are running too
+    struct BarrierTool(Arc<tokio::sync::Barrier>);
+
+    #[async_trait::async_trait]
+    impl ToolFunction for BarrierTool {
+        async fn call(&self, args: Value) -> Result<Value> {
+            self.0.wait().await;
+            Ok(args)
+        }
+    }
+
+    fn action(id: Option<&str>, tool: &str, input: Value) -> Action {
+        Action {
+            id: id.map(str::to_string),
+            tool: tool.to_string(),
+            input,
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_tools_concurrently() {
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), IdleAgent, None);
+        agent.add_tool("wait", BarrierTool(Arc::new(tokio::sync::Barrier::new(2))));
+
+        let actions = [
+            action(Some("a"), "wait", json!(1)),
+            action(None, "wait", json!(2)),
+            action(None, "missing", json!({})),
+        ];
+
+        // run one after the other, the calls would wait on the barrier forever
+        let observation = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            agent.execute_tools(&actions.iter().collect::<Vec<_>>()),
+        )
+        .await
+        .expect("tool calls should overlap");
+
+        let observation: Value = serde_json::from_str(&observation).unwrap();
+        assert_eq!(observation["a"]["output"], json!(1));
+        assert_eq!(observation["call_2"]["output"], json!(2));
+        assert!(observation["call_3"]["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn keep_observations_of_repeated_ids() {
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), IdleAgent, None);
+        agent.add_tool("echo", EchoTool);
+
+        let actions = [
+            action(Some("call_2"), "echo", json!(1)),
+            action(None, "echo", json!(2)),
+            action(Some("call_2"), "echo", json!(3)),
+        ];
+        let observation = agent
+            .execute_tools(&actions.iter().collect::<Vec<_>>())
+            .await;
+
+        let observation: Value = serde_json::from_str(&observation).unwrap();
+        assert_eq!(observation["call_2"]["output"], json!(1));
+        assert_eq!(observation["call_2_2"]["output"], json!(2));
+        assert_eq!(observation["call_2_3"]["output"], json!(3));
+    }
+
+    struct RejectAll;
+
+    #[async_trait::async_trait]
+    impl Approver for RejectAll {
+        async fn review(&self, _tool_name: &str, _args: &Value) -> ApprovalDecision {
+            ApprovalDecision::Reject("not allowed".to_string())
+        }
+    }
+
+    struct EchoTool;
+
+    #[async_trait::async_trait]
+    impl ToolFunction for EchoTool {
+        async fn call(&self, args: Value) -> Result<Value> {
+            Ok(args)
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_high_risk_call() {
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), IdleAgent, None);
+        agent.add_tool("echo", EchoTool);
+        agent.add_tool_with_policy(
+            "risky_echo",
+            EchoTool,
+            ToolPolicy {
+                risk: RiskLevel::High,
+                ..Default::default()
+            },
+        );
+        agent.set_approver(RejectAll);
+
+        let observation = agent.execute_tool("echo", &json!("hi")).await.unwrap();
+        assert_eq!(observation, "\"hi\"");
+
+        let observation = agent
+            .execute_tool("risky_echo", &json!("hi"))
+            .await
+            .unwrap();
+        assert_eq!(observation, "Tool call rejected: not allowed");
+    }
+
+    #[tokio::test]
+    async fn reject_high_risk_call_without_approver() {
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), IdleAgent, None);
+        agent.add_tool_with_policy(
+            "risky_echo",
+            EchoTool,
+            ToolPolicy {
+                risk: RiskLevel::High,
+                ..Default::default()
+            },
+        );
+
+        let observation = agent
+            .execute_tool("risky_echo", &json!("hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            observation,
+            "Tool call rejected: risky_echo needs approval and no approver is configured"
+        );
+    }
+
+    /// Records the prompt of a step like `BaseAgent`, then never replies
+    #[derive(Default)]
+    struct StuckAgent {
+        messages: tokio::sync::Mutex<Vec<ChatCompletionRequestMessage>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Agent for StuckAgent {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+
+        fn description(&self) -> &str {
+            "never replies"
+        }
+
+        async fn step<'a>(&self, message: &'a str) -> Result<String> {
+            self.messages
+                .lock()
+                .await
+                .push(ChatCompletionRequestMessage::User(message.into()));
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            Err(AgentError::Generic("No reply".to_string()))
+        }
+
+        async fn history(&self) -> Vec<ChatCompletionRequestMessage> {
+            self.messages.lock().await.clone()
+        }
+
+        async fn restore_history(&self, messages: Vec<ChatCompletionRequestMessage>) {
+            *self.messages.lock().await = messages;
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_in_flight_step() {
+        let mut agent = ReactAgent::new(
+            "test".to_string(),
+            "test".to_string(),
+            StuckAgent::default(),
+            None,
+        );
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            trigger.cancel();
+        });
+
+        let outcome = agent
+            .react_loop_with_cancel("What is the weather like?", cancel)
+            .await
+            .unwrap();
+        match outcome {
+            ReactOutcome::Cancelled(transcript) => assert_eq!(
+                transcript,
+                vec![TranscriptEntry::Prompt(
+                    "What is the weather like?".to_string()
+                )]
+            ),
+            ReactOutcome::Answer(_) => panic!("run should be cancelled"),
+        }
+        // the prompt of the cancelled step is rolled back
+        assert!(agent.agent.history().await.is_empty());
+    }
+
+    struct ScriptedAgent {
+        replies: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Agent for ScriptedAgent {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn description(&self) -> &str {
+            "replies from a script"
+        }
+
+        async fn step<'a>(&self, _message: &'a str) -> Result<String> {
+            Ok(self.replies.lock().unwrap().remove(0))
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_pending_action() {
+        use crate::agent::checkpoint::SqliteCheckpointStore;
+
+        let store = SqliteCheckpointStore::open_in_memory().unwrap();
+        store
+            .save(&Checkpoint {
+                run_id: "run-1".to_string(),
+                interactions: 1,
+                messages: Vec::new(),
+                transcript: Vec::new(),
+                next_prompt: "What is the weather like?".to_string(),
+                pending_action: Some(
+                    r#"{"state": "pause", "thought": "echo", "action": {"tool": "echo", "input": "sunny"}}"#
+                        .to_string(),
+                ),
+                answer: None,
+                answer_schema: None,
+            })
+            .await
+            .unwrap();
+
+        let agent = ScriptedAgent {
+            replies: std::sync::Mutex::new(vec![
+                r#"{"state": "answer", "thought": "It is sunny."}"#.to_string(),
+            ]),
+        };
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), agent, None);
+        agent.add_tool("echo", EchoTool);
+        agent.set_checkpoint_store(store);
+
+        let answer = agent.resume("run-1").await.unwrap();
+        assert_eq!(answer, "It is sunny.");
+        assert_eq!(
+            agent.transcript()[0],
+            TranscriptEntry::Prompt("**Observation**: \"sunny\"".to_string())
+        );
+
+        // a finished run resumes straight to its answer
+        assert_eq!(agent.resume("run-1").await.unwrap(), "It is sunny.");
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct WeatherReport {
+        city: String,
+        temperature: f32,
+    }
+
+    #[tokio::test]
+    async fn reprompt_until_typed_answer() {
+        let agent = ScriptedAgent {
+            replies: std::sync::Mutex::new(vec![
+                r#"{"state": "answer", "thought": "done", "answer": {"city": "Paris"}}"#
+                    .to_string(),
+                r#"{"state": "answer", "thought": "done", "answer": {"city": "Paris", "temperature": 21.5}}"#
+                    .to_string(),
+            ]),
+        };
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), agent, None);
+
+        let report = agent
+            .react_loop_typed::<WeatherReport>("What is the weather like in Paris?")
+            .await
+            .unwrap();
+        assert_eq!(report.city, "Paris");
+        assert_eq!(report.temperature, 21.5);
+        assert!(matches!(
+            &agent.transcript()[2],
+            TranscriptEntry::Prompt(prompt) if prompt.contains("missing field `temperature`")
+        ));
+    }
+
+    #[tokio::test]
+    async fn resume_typed_run() {
+        use crate::agent::checkpoint::SqliteCheckpointStore;
+
+        let store = SqliteCheckpointStore::open_in_memory().unwrap();
+        store
+            .save(&Checkpoint {
+                run_id: "run-1".to_string(),
+                interactions: 1,
+                messages: Vec::new(),
+                transcript: Vec::new(),
+                next_prompt: "**Observation**: 21.5".to_string(),
+                pending_action: None,
+                answer: None,
+                answer_schema: Some(answer_schema::<WeatherReport>().unwrap()),
+            })
+            .await
+            .unwrap();
+
+        let agent = ScriptedAgent {
+            replies: std::sync::Mutex::new(vec![
+                r#"{"state": "answer", "thought": "done", "answer": "21.5 in Paris"}"#.to_string(),
+                r#"{"state": "answer", "thought": "done", "answer": {"city": "Paris", "temperature": 21.5}}"#
+                    .to_string(),
+            ]),
+        };
+        let mut agent = ReactAgent::new("test".to_string(), "test".to_string(), agent, None);
+        agent.set_checkpoint_store(store);
+
+        assert!(agent.resume("run-1").await.is_err());
+        let report = agent.resume_typed::<WeatherReport>("run-1").await.unwrap();
+        assert_eq!(report.city, "Paris");
+        assert!(matches!(
+            &agent.transcript()[2],
+            TranscriptEntry::Prompt(prompt) if prompt.contains("does not match")
+        ));
     }
 }
@@ -0,0 +1,254 @@
+use std::str::FromStr;
+
+use crate::error::AgentError;
+use serde_json::{Map, Value};
+
+use super::tool::{FunctionSchemaStyle, ToolDefinition};
+
+/// How tool schemas are written into the system prompt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolRenderer {
+    /// Pretty-printed JSON schema
+    #[default]
+    PrettyJson,
+    /// JSON schema on a single line
+    CompactJson,
+    /// TypeScript-like function signature
+    TypeScript,
+    /// One bullet per parameter
+    Bullets,
+}
+
+impl FromStr for ToolRenderer {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" | "pretty-json" => Ok(ToolRenderer::PrettyJson),
+            "compact" | "compact-json" => Ok(ToolRenderer::CompactJson),
+            "typescript" | "ts" => Ok(ToolRenderer::TypeScript),
+            "bullets" => Ok(ToolRenderer::Bullets),
+            _ => Err(AgentError::Generic(format!(
+                "Unknown tool renderer: {}, expected pretty, compact, typescript or bullets",
+                s
+            ))),
+        }
+    }
+}
+
+impl ToolRenderer {
+    pub fn render(&self, tool: &ToolDefinition) -> String {
+        match self {
+            ToolRenderer::PrettyJson => {
+                serde_json::to_string_pretty(&tool.to_schema(FunctionSchemaStyle::Legacy)).unwrap()
+            }
+            ToolRenderer::CompactJson => tool.to_schema(FunctionSchemaStyle::Legacy).to_string(),
+            ToolRenderer::TypeScript => render_typescript(tool),
+            ToolRenderer::Bullets => render_bullets(tool),
+        }
+    }
+}
+
+/// Parameters of a tool as (name, schema, required), sorted by name as serde_json keeps
+/// object keys sorted
+fn parameters(tool: &ToolDefinition) -> Vec<(&String, &Value, bool)> {
+    let required: Vec<&str> = tool
+        .parameters
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    tool.parameters
+        .get("properties")
+        .and_then(Value::as_object)
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, schema)| (name, schema, required.contains(&name.as_str())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn render_typescript(tool: &ToolDefinition) -> String {
+    let definitions = tool
+        .parameters
+        .get("definitions")
+        .and_then(Value::as_object);
+
+    let mut fields = String::new();
+    for (name, schema, required) in parameters(tool) {
+        if let Some(description) = schema.get("description").and_then(Value::as_str) {
+            fields.push_str(&format!("  // {}\n", description));
+        }
+        fields.push_str(&format!(
+            "  {}{}: {};\n",
+            name,
+            if required { "" } else { "?" },
+            type_name(schema, definitions)
+        ));
+    }
+
+    format!("{}(args: {{\n{}}})", tool.name, fields)
+}
+
+fn render_bullets(tool: &ToolDefinition) -> String {
+    let definitions = tool
+        .parameters
+        .get("definitions")
+        .and_then(Value::as_object);
+
+    parameters(tool)
+        .into_iter()
+        .map(|(name, schema, required)| {
+            let description = schema
+                .get("description")
+                .and_then(Value::as_str)
+                .map(|d| format!(": {}", d))
+                .unwrap_or_default();
+            format!(
+                "  * {} ({}{}){}",
+                name,
+                type_name(schema, definitions),
+                if required { ", required" } else { "" },
+                description
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// TypeScript-like name of the type described by a JSON schema
+fn type_name(schema: &Value, definitions: Option<&Map<String, Value>>) -> String {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let resolved = reference
+            .rsplit('/')
+            .next()
+            .and_then(|name| definitions.and_then(|d| d.get(name)));
+        return match resolved {
+            Some(resolved) => type_name(resolved, definitions),
+            None => "any".to_string(),
+        };
+    }
+
+    if let Some(variants) = schema.get("enum").and_then(Value::as_array) {
+        return variants
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<String>>()
+            .join(" | ");
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(variants) = schema.get(key).and_then(Value::as_array) {
+            return variants
+                .iter()
+                .map(|variant| type_name(variant, definitions))
+                .collect::<Vec<String>>()
+                .join(" | ");
+        }
+    }
+
+    match schema.get("type") {
+        Some(Value::String(ty)) => primitive_name(ty, schema, definitions),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|ty| primitive_name(ty, schema, definitions))
+            .collect::<Vec<String>>()
+            .join(" | "),
+        _ => "any".to_string(),
+    }
+}
+
+fn primitive_name(ty: &str, schema: &Value, definitions: Option<&Map<String, Value>>) -> String {
+    match ty {
+        "integer" | "number" => "number".to_string(),
+        "array" => match schema.get("items") {
+            Some(items) => format!("{}[]", type_name(items, definitions)),
+            None => "any[]".to_string(),
+        },
+        "object" => match schema.get("properties").and_then(Value::as_object) {
+            Some(properties) => format!(
+                "{{ {} }}",
+                properties
+                    .iter()
+                    .map(|(name, property)| format!(
+                        "{}: {}",
+                        name,
+                        type_name(property, definitions)
+                    ))
+                    .collect::<Vec<String>>()
+                    .join("; ")
+            ),
+            None => "object".to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GetGeoLocationArgs, GetWeatherArgs};
+
+    fn tools() -> Vec<ToolDefinition> {
+        vec![
+            ToolDefinition::new::<GetWeatherArgs>(
+                "get_weather",
+                "Get current weather of the location",
+            ),
+            ToolDefinition::new::<GetGeoLocationArgs>(
+                "get_geo_location",
+                "Get the latitude and longitude of a city",
+            ),
+        ]
+    }
+
+    /// Tokens estimated as a quarter of the characters, the usual rule of thumb of BPE
+    /// tokenizers for English text and JSON
+    fn estimate_tokens(text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+
+    #[test]
+    fn render_typescript_signature() {
+        let rendered = ToolRenderer::TypeScript.render(&tools()[0]);
+        assert!(rendered.starts_with("get_weather(args: {"));
+        assert!(rendered.contains("  city: string;"));
+        assert!(rendered.contains(r#"  unit?: "Celsius" | "Fahrenheit";"#));
+    }
+
+    #[test]
+    fn render_bullets() {
+        let rendered = ToolRenderer::Bullets.render(&tools()[1]);
+        assert_eq!(
+            rendered,
+            "  * city (string, required): the name of the city"
+        );
+    }
+
+    #[test]
+    fn token_footprint() {
+        let footprint = |renderer: ToolRenderer| -> usize {
+            tools()
+                .iter()
+                .map(|tool| estimate_tokens(&renderer.render(tool)))
+                .sum()
+        };
+
+        let pretty = footprint(ToolRenderer::PrettyJson);
+        let compact = footprint(ToolRenderer::CompactJson);
+        let typescript = footprint(ToolRenderer::TypeScript);
+        let bullets = footprint(ToolRenderer::Bullets);
+        println!(
+            "tokens: pretty {}, compact {}, typescript {}, bullets {}",
+            pretty, compact, typescript, bullets
+        );
+
+        assert!(compact < pretty);
+        assert!(typescript < compact);
+        assert!(bullets < compact);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::error::AgentError;
 use crate::prelude::*;
 use async_trait::async_trait;
-use schemars::{JsonSchema, schema_for};
+use schemars::{JsonSchema, r#gen::SchemaSettings};
 use serde_json::{Value, json};
 
 #[async_trait]
@@ -8,6 +9,14 @@ pub trait ToolFunction: Send + Sync {
     async fn call(&self, args: Value) -> Result<Value>;
 }
 
+/// Shared tools, e.g. loaded at runtime, can be registered like owned ones
+#[async_trait]
+impl<T: ToolFunction + ?Sized> ToolFunction for std::sync::Arc<T> {
+    async fn call(&self, args: Value) -> Result<Value> {
+        (**self).call(args).await
+    }
+}
+
 /// API Category for tool functions
 #[derive(Debug, Clone, Copy)]
 pub enum FunctionSchemaStyle {
@@ -15,13 +24,87 @@ pub enum FunctionSchemaStyle {
     Tool,   // for assistants/responses API（tools filed）
 }
 
-/// Generic Function Schema Generator
-pub fn build_function_schema<T: JsonSchema>(
-    name: &str,
-    description: &str,
-    style: FunctionSchemaStyle,
-) -> Value {
-    let schema = schema_for!(T);
+/// A tool as declared to the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments
+    pub parameters: Value,
+    /// Example input (object) or whole example step (string) for the example session
+    pub example: Option<Value>,
+}
+
+impl ToolDefinition {
+    pub fn new<T: JsonSchema>(name: &str, description: &str) -> Self {
+        ToolDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: parameters_schema::<T>(),
+            example: None,
+        }
+    }
+
+    pub fn with_example(mut self, example: Value) -> Self {
+        self.example = Some(example);
+        self
+    }
+
+    /// Parse a function schema of either style
+    pub fn from_schema(schema: &Value) -> Result<Self> {
+        let function = match schema.get("function") {
+            Some(function) if function.is_object() => function,
+            _ => schema,
+        };
+
+        let name = function
+            .get("name")
+            .and_then(Value::as_str)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| {
+                AgentError::Generic(format!("Tool schema has no \"name\": {}", schema))
+            })?;
+
+        let description = function
+            .get("description")
+            .and_then(Value::as_str)
+            .ok_or_else(|| AgentError::Generic(format!("Tool {} has no \"description\"", name)))?;
+
+        let parameters = match function.get("parameters") {
+            None => json!({ "type": "object", "properties": {} }),
+            Some(parameters) if parameters.is_object() => parameters.clone(),
+            Some(_) => {
+                return Err(AgentError::Generic(format!(
+                    "Parameters of tool {} must be a JSON object",
+                    name
+                )));
+            }
+        };
+
+        Ok(ToolDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+            example: function.get("example").cloned(),
+        })
+    }
+
+    pub fn to_schema(&self, style: FunctionSchemaStyle) -> Value {
+        function_schema(
+            &self.name,
+            &self.description,
+            self.parameters.clone(),
+            style,
+        )
+    }
+}
+
+fn parameters_schema<T: JsonSchema>() -> Value {
+    // inline nested types, the schema must be self-contained once written into a prompt
+    let schema = SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<T>();
     let mut parameters = serde_json::to_value(schema.schema).unwrap();
 
     if let Some(obj) = parameters.as_object_mut() {
@@ -29,6 +112,24 @@ pub fn build_function_schema<T: JsonSchema>(
         obj.entry("type").or_insert(json!("object"));
     }
 
+    parameters
+}
+
+/// Generic Function Schema Generator
+pub fn build_function_schema<T: JsonSchema>(
+    name: &str,
+    description: &str,
+    style: FunctionSchemaStyle,
+) -> Value {
+    function_schema(name, description, parameters_schema::<T>(), style)
+}
+
+fn function_schema(
+    name: &str,
+    description: &str,
+    parameters: Value,
+    style: FunctionSchemaStyle,
+) -> Value {
     match style {
         FunctionSchemaStyle::Legacy => {
             json!({
@@ -50,6 +151,21 @@ pub fn build_function_schema<T: JsonSchema>(
     }
 }
 
+/// Attach an example to a function schema, shown in the example session of the system prompt.
+/// An object is used as the example input of the tool, a string as the whole example step.
+pub fn attach_example(mut schema: Value, example: Value) -> Value {
+    let function = match schema.get_mut("function") {
+        Some(function) => function,
+        None => &mut schema,
+    };
+
+    if let Some(obj) = function.as_object_mut() {
+        obj.insert("example".to_string(), example);
+    }
+
+    schema
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -93,4 +209,37 @@ mod tests {
             serde_json::to_string_pretty(&schema).unwrap()
         );
     }
+
+    #[test]
+    fn parse_both_schema_styles() {
+        let legacy = build_function_schema::<GetWeatherArgs>(
+            "get_weather",
+            "Get current weather",
+            FunctionSchemaStyle::Legacy,
+        );
+        let tool = build_function_schema::<GetWeatherArgs>(
+            "get_weather",
+            "Get current weather",
+            FunctionSchemaStyle::Tool,
+        );
+
+        let from_legacy = ToolDefinition::from_schema(&legacy).unwrap();
+        let from_tool = ToolDefinition::from_schema(&tool).unwrap();
+        assert_eq!(from_legacy, from_tool);
+        assert_eq!(
+            from_legacy,
+            ToolDefinition::new::<GetWeatherArgs>("get_weather", "Get current weather")
+        );
+        assert_eq!(from_tool.to_schema(FunctionSchemaStyle::Tool), tool);
+    }
+
+    #[test]
+    fn reject_incomplete_schema() {
+        let no_name = json!({ "description": "Get current weather" });
+        let no_description = json!({ "type": "function", "function": { "name": "get_weather" } });
+
+        assert!(ToolDefinition::from_schema(&no_name).is_err());
+        let err = ToolDefinition::from_schema(&no_description).unwrap_err();
+        assert!(err.to_string().contains("get_weather"));
+    }
 }
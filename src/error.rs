@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use async_openai::error::OpenAIError;
 use thiserror::Error;
 
@@ -11,4 +13,10 @@ pub enum AgentError {
 
     #[error("HTTP error: {0}")]
     HTTPError(#[from] reqwest::Error),
+
+    #[error("Tool {0} timed out after {1:?}")]
+    Timeout(String, Duration),
+
+    #[error("Tool {0} is unavailable after {1} consecutive failures")]
+    CircuitOpen(String, u32),
 }
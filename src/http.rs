@@ -0,0 +1,153 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::error::AgentError;
+use crate::prelude::*;
+use reqwest::{
+    Certificate, Client, ClientBuilder, Proxy,
+    dns::Resolve,
+    header::{HeaderMap, HeaderName, HeaderValue},
+    redirect,
+};
+
+/// Settings of the HTTP client shared by tools
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// Total timeout of a single request
+    pub timeout: Duration,
+    /// Timeout of the connect phase
+    pub connect_timeout: Duration,
+    /// How long idle connections are kept in the pool
+    pub pool_idle_timeout: Duration,
+    /// Max idle connections per host kept in the pool
+    pub pool_max_idle_per_host: usize,
+    /// Value of the `User-Agent` header
+    pub user_agent: String,
+    /// Proxy url for all requests, e.g. "http://127.0.0.1:7890", system proxy is used if None
+    pub proxy: Option<String>,
+    /// Extra root certificates in PEM format
+    pub ca_certificates: Vec<PathBuf>,
+    /// Headers sent with every request
+    pub default_headers: Vec<(String, String)>,
+    /// Follow up to 10 redirects, otherwise redirect responses are returned as they are
+    pub follow_redirects: bool,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 8,
+            user_agent: format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            proxy: None,
+            ca_certificates: Vec::new(),
+            default_headers: Vec::new(),
+            follow_redirects: true,
+        }
+    }
+}
+
+/// HTTP context injected into tools, cheap to clone and reused across calls
+/// so that connections are pooled.
+#[derive(Debug, Clone)]
+pub struct HttpContext {
+    client: Client,
+}
+
+impl HttpContext {
+    pub fn new(config: HttpConfig) -> Result<Self> {
+        Ok(HttpContext {
+            client: HttpContext::builder(config)?.build()?,
+        })
+    }
+
+    /// Resolve host names with `resolver`, e.g. to vet the addresses connected to
+    pub fn with_resolver<R: Resolve + 'static>(
+        config: HttpConfig,
+        resolver: Arc<R>,
+    ) -> Result<Self> {
+        Ok(HttpContext {
+            client: HttpContext::builder(config)?
+                .dns_resolver(resolver)
+                .build()?,
+        })
+    }
+
+    fn builder(config: HttpConfig) -> Result<ClientBuilder> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.default_headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| AgentError::Generic(format!("Invalid header name {}: {}", name, e)))?;
+            let value = HeaderValue::from_str(value).map_err(|e| {
+                AgentError::Generic(format!("Invalid header value for {}: {}", name, e))
+            })?;
+            headers.insert(name, value);
+        }
+
+        let mut builder = Client::builder()
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .user_agent(config.user_agent)
+            .default_headers(headers);
+
+        if !config.follow_redirects {
+            builder = builder.redirect(redirect::Policy::none());
+        }
+
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        for path in &config.ca_certificates {
+            let pem = std::fs::read(path).map_err(|e| {
+                AgentError::Generic(format!(
+                    "Failed to read CA certificate {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+
+        Ok(builder)
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Default for HttpContext {
+    fn default() -> Self {
+        HttpContext::new(HttpConfig::default()).expect("Failed to build default HTTP client")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_with_config() {
+        let config = HttpConfig {
+            proxy: Some("http://127.0.0.1:7890".to_string()),
+            default_headers: vec![("X-Request-Source".to_string(), "agent".to_string())],
+            ..Default::default()
+        };
+
+        assert!(HttpContext::new(config).is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_header() {
+        let config = HttpConfig {
+            default_headers: vec![("bad header".to_string(), "x".to_string())],
+            ..Default::default()
+        };
+
+        assert!(HttpContext::new(config).is_err());
+    }
+}
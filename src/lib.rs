@@ -1,7 +1,12 @@
 pub mod agent;
 pub mod error;
+pub mod http;
+pub mod mcp;
+pub mod openapi;
 pub mod prelude;
+pub mod subprocess;
 pub mod tools;
+pub mod wasm;
 
 pub use tools::geo::*;
 pub use tools::weather::*;
@@ -1,50 +1,180 @@
-use std::env;
+use std::{env, sync::Arc, time::Duration};
 
-use clap::{Parser, command};
+use clap::{Parser, Subcommand};
 use colored::Colorize;
 use reactagent::{
     GetGeoLocationArgs, GetGeoLocationTool, GetWeatherArgs, GetWeatherTool,
     agent::{
+        approval::{ApprovalDecision, Approver},
         base::BaseAgent,
-        prompt::create_system_prompt,
-        react::ReactAgent,
-        tool::{FunctionSchemaStyle, build_function_schema},
+        cache::{CacheStore, CachedTool, DiskCache, MemoryCache},
+        checkpoint::FileCheckpointStore,
+        policy::{CircuitBreakerPolicy, PolicyTool, RetryPolicy, ToolPolicy},
+        prompt::{Locale, PromptOptions, PromptTemplate, create_system_prompt_with_options},
+        react::{ReactAgent, ReactOutcome},
+        render::ToolRenderer,
+        tool::ToolDefinition,
     },
+    error::AgentError,
+    http::{HttpConfig, HttpContext},
+    mcp::{client::McpClient, server::McpServer},
     prelude::Result,
+    subprocess::load_tools_dir,
+    tools::{
+        calc::{CalculateArgs, CalculateTool},
+        fetch::{FetchConfig, FetchUrlArgs, FetchUrlTool},
+        fs::{FsConfig, fs_tools},
+        shell::{RunShellArgs, RunShellTool, ShellConfig, split_command},
+        sql::{QuerySqlTool, SqlConfig},
+    },
+    wasm::{WasmLimits, load_plugins_dir},
 };
+use tokio_util::sync::CancellationToken;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-    /// Region name to check current weather (required)
-    location: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Region name to check current weather (required unless resuming a run)
+    #[arg(required_unless_present = "resume")]
+    location: Option<String>,
     /// Load the .env at the specified absolute path, otherwise, it will load the .env
     /// located in the environment's current directory or its parents in sequence.
     #[arg(short, long)]
     dotenv_absolute_path: Option<String>,
+    /// Directory to cache geo location lookups across runs, cached in memory if not set
+    #[arg(short, long)]
+    cache_dir: Option<String>,
+    /// Directory to save a checkpoint of the run after every step
+    #[arg(long)]
+    checkpoint_dir: Option<String>,
+    /// Jinja-style template file replacing the default system prompt
+    #[arg(long)]
+    prompt_template: Option<String>,
+    /// How tool schemas are written into the prompt: pretty, compact, typescript or bullets
+    #[arg(long, default_value = "pretty")]
+    tool_format: ToolRenderer,
+    /// Language of the built-in system prompt: en or zh-CN
+    #[arg(long, default_value = "en")]
+    lang: Locale,
+    /// Command line of an MCP server to start and use the tools of over stdio, repeatable
+    #[arg(long)]
+    mcp_server: Vec<String>,
+    /// Url of an MCP server to use the tools of over HTTP, repeatable
+    #[arg(long)]
+    mcp_url: Vec<String>,
+    /// Directory of manifests of tools implemented as external commands
+    #[arg(long)]
+    tools_dir: Option<String>,
+    /// Directory of WASM plugin tools, run sandboxed without file system or network access
+    #[arg(long)]
+    plugins_dir: Option<String>,
+    /// Enable the run_shell tool, running commands in this directory after approval
+    #[arg(long)]
+    shell_dir: Option<String>,
+    /// Programs the run_shell tool may run, comma separated
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "ls,cat,head,tail,wc,grep"
+    )]
+    shell_allow: Vec<String>,
+    /// Enable the read_file, list_dir, write_file and search_files tools, confined to this directory
+    #[arg(long)]
+    fs_root: Option<String>,
+    /// Let write_file report what it would write instead of writing
+    #[arg(long, requires = "fs_root")]
+    fs_dry_run: bool,
+    /// Enable the fetch_url tool, reading web pages as text
+    #[arg(long)]
+    fetch: bool,
+    /// Domains fetch_url may read from, comma separated, any domain if not set
+    #[arg(long, value_delimiter = ',', requires = "fetch")]
+    fetch_allow: Vec<String>,
+    /// Domains fetch_url never reads from, comma separated
+    #[arg(long, value_delimiter = ',', requires = "fetch")]
+    fetch_deny: Vec<String>,
+    /// Let fetch_url read from loopback, link-local and private addresses too
+    #[arg(long, requires = "fetch")]
+    fetch_allow_private: bool,
+    /// Enable the query_sql tool, running read-only queries against this SQLite database
+    #[arg(long)]
+    sql_db: Option<String>,
+    /// Let query_sql change the database too, every statement then needs approval
+    #[arg(long, requires = "sql_db")]
+    sql_allow_writes: bool,
+    /// Resume the run with this id from its last checkpoint
+    #[arg(long, requires = "checkpoint_dir")]
+    resume: Option<String>,
 }
 
-async fn crate_base_agent() -> Result<BaseAgent> {
-    let tools = [
-        build_function_schema::<GetWeatherArgs>(
-            "get_weather",
-            "Get current weather of the location",
-            FunctionSchemaStyle::Legacy,
-        ),
-        build_function_schema::<GetGeoLocationArgs>(
-            "get_geo_location",
-            "Get the latitude and longitude of a city",
-            FunctionSchemaStyle::Legacy,
-        ),
-    ];
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve the tools of the agent as an MCP server over stdio instead of running it
+    McpServe {
+        /// Serve high risk tools like run_shell and write_file too, their calls are not approved
+        #[arg(long)]
+        serve_high_risk: bool,
+    },
+}
 
-    let system_prompt = create_system_prompt(
-        tools
-            .iter()
-            .map(|t| serde_json::to_string_pretty(t).unwrap())
-            .collect::<Vec<String>>(),
-        None,
-    );
+const WEATHER_DESCRIPTION: &str = "Get current weather of the location";
+const GEO_LOCATION_DESCRIPTION: &str = "Get the latitude and longitude of a city";
+const CALCULATE_DESCRIPTION: &str = "Evaluate a math expression exactly or convert units, \
+    e.g. temperatures with \"21.5 degC to degF\"; results of functions like sqrt or sin are \
+    marked approximate";
+
+/// Ask on the terminal whether a high risk tool call may run, rejecting it once the run
+/// is cancelled
+struct CliApprover {
+    cancel: CancellationToken,
+}
+
+#[async_trait::async_trait]
+impl Approver for CliApprover {
+    async fn review(&self, tool_name: &str, args: &serde_json::Value) -> ApprovalDecision {
+        println!(
+            "\n{} {} with args: {}",
+            "Approval required:".bold().yellow(),
+            tool_name.italic(),
+            args.to_string().italic()
+        );
+        println!("[y] approve, [n <reason>] reject, [e <json args>] edit");
+
+        // a detached thread, unlike spawn_blocking, does not hold up the shutdown of the
+        // runtime when the run is cancelled while the prompt waits for input
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        std::thread::spawn(move || {
+            let mut line = String::new();
+            let _ = sender.send(std::io::stdin().read_line(&mut line).map(|_| line));
+        });
+
+        let line = tokio::select! {
+            _ = self.cancel.cancelled() => {
+                return ApprovalDecision::Reject("Run cancelled".to_string());
+            }
+            line = receiver => line,
+        };
+        let line = match line {
+            Ok(Ok(line)) => line.trim().to_string(),
+            _ => return ApprovalDecision::Reject("Failed to read the approval".to_string()),
+        };
+
+        match line.split_once(' ').unwrap_or((line.as_str(), "")) {
+            ("y", _) => ApprovalDecision::Approve,
+            ("e", edited) => match serde_json::from_str(edited) {
+                Ok(edited_args) => ApprovalDecision::Edit(edited_args),
+                Err(e) => ApprovalDecision::Reject(format!("Invalid edited arguments: {}", e)),
+            },
+            ("n", reason) if !reason.is_empty() => ApprovalDecision::Reject(reason.to_string()),
+            _ => ApprovalDecision::Reject("Rejected by the user".to_string()),
+        }
+    }
+}
+
+async fn crate_base_agent(options: &PromptOptions, tools: &[ToolDefinition]) -> Result<BaseAgent> {
+    let system_prompt = create_system_prompt_with_options(tools, options)?;
 
     let base_agent = BaseAgent::new(
         "Base Agent",
@@ -58,6 +188,147 @@ async fn crate_base_agent() -> Result<BaseAgent> {
     Ok(base_agent)
 }
 
+// both tools only issue GET requests, so they are safe to retry
+fn tool_policy() -> ToolPolicy {
+    ToolPolicy {
+        retry: Some(RetryPolicy::default()),
+        circuit_breaker: Some(CircuitBreakerPolicy::default()),
+        ..Default::default()
+    }
+}
+
+// the coordinates of a city never change, so they are worth caching
+fn geo_location_tool(http: HttpContext, cache_dir: Option<&str>) -> CachedTool {
+    let cache: Arc<dyn CacheStore> = match cache_dir {
+        Some(dir) => Arc::new(DiskCache::new(dir)),
+        None => Arc::new(MemoryCache::new()),
+    };
+    CachedTool::new(
+        "get_geo_location",
+        GetGeoLocationTool::new(http),
+        cache,
+        Duration::from_secs(7 * 24 * 3600),
+    )
+}
+
+/// The tools enabled by the arguments with their definitions and policies, loaded up front
+/// as the definitions go into the system prompt
+async fn agent_tools(args: &Args, http: HttpContext) -> Result<Vec<PolicyTool>> {
+    let mut tools: Vec<PolicyTool> = vec![
+        (
+            ToolDefinition::new::<GetWeatherArgs>("get_weather", WEATHER_DESCRIPTION),
+            Arc::new(GetWeatherTool::new(http.clone())),
+            tool_policy(),
+        ),
+        (
+            ToolDefinition::new::<GetGeoLocationArgs>("get_geo_location", GEO_LOCATION_DESCRIPTION),
+            Arc::new(geo_location_tool(http.clone(), args.cache_dir.as_deref())),
+            tool_policy(),
+        ),
+        (
+            ToolDefinition::new::<CalculateArgs>("calculate", CALCULATE_DESCRIPTION),
+            Arc::new(CalculateTool),
+            ToolPolicy::default(),
+        ),
+    ];
+
+    if let Some(ref dir) = args.shell_dir {
+        let allowed: Vec<&str> = args.shell_allow.iter().map(String::as_str).collect();
+        let definition = ToolDefinition::new::<RunShellArgs>(
+            "run_shell",
+            &format!(
+                "Run a command in the working directory and get its exit code and output, allowed programs: {}",
+                allowed.join(", ")
+            ),
+        );
+        let shell = RunShellTool::new(ShellConfig::new(dir, &allowed));
+        tools.push((definition, Arc::new(shell), RunShellTool::policy()));
+    }
+    if let Some(ref root) = args.fs_root {
+        let mut config = FsConfig::new(root);
+        config.dry_run = args.fs_dry_run;
+        tools.extend(fs_tools(config)?);
+    }
+    if args.fetch {
+        let definition = ToolDefinition::new::<FetchUrlArgs>(
+            "fetch_url",
+            "Read a web page or text file from the internet, HTML is returned as markdown",
+        );
+        let config = FetchConfig {
+            allowed_domains: args.fetch_allow.clone(),
+            denied_domains: args.fetch_deny.clone(),
+            allow_private_addresses: args.fetch_allow_private,
+            ..Default::default()
+        };
+        let fetch = FetchUrlTool::new(config, HttpConfig::default())?;
+        tools.push((definition, Arc::new(fetch), ToolPolicy::default()));
+    }
+    if let Some(ref path) = args.sql_db {
+        let mut config = SqlConfig::new(path);
+        config.allow_writes = args.sql_allow_writes;
+        let sql = QuerySqlTool::open(config)?;
+        let (definition, policy) = (sql.definition()?, sql.policy());
+        tools.push((definition, Arc::new(sql), policy));
+    }
+
+    if let Some(ref dir) = args.tools_dir {
+        for (definition, tool) in load_tools_dir(dir)? {
+            let policy = tool.policy();
+            tools.push((definition, Arc::new(tool), policy));
+        }
+    }
+    if let Some(ref dir) = args.plugins_dir {
+        for (definition, tool) in load_plugins_dir(dir, WasmLimits::default())? {
+            tools.push((definition, Arc::new(tool), ToolPolicy::default()));
+        }
+    }
+
+    let mut mcp_clients = Vec::new();
+    for command_line in &args.mcp_server {
+        let words = split_command(command_line)?;
+        let (command, command_args) = words.split_first().ok_or_else(|| {
+            AgentError::Generic("Empty command line of an MCP server".to_string())
+        })?;
+        mcp_clients.push(McpClient::stdio(command, command_args).await?);
+    }
+    for url in &args.mcp_url {
+        mcp_clients.push(McpClient::http(http.clone(), url).await?);
+    }
+    for client in mcp_clients {
+        for (definition, tool) in Arc::new(client).tools().await? {
+            tools.push((definition, Arc::new(tool), ToolPolicy::default()));
+        }
+    }
+
+    // a second tool of the same name would silently replace the first one when registered
+    let mut names = std::collections::HashSet::new();
+    if let Some((clash, _, _)) = tools.iter().find(|(tool, _, _)| !names.insert(&tool.name)) {
+        return Err(AgentError::Generic(format!(
+            "Tool {} is defined twice, rename one of them",
+            clash.name
+        )));
+    }
+
+    Ok(tools)
+}
+
+/// Serve the tools the agent would use over stdio, stdout carries the protocol so nothing
+/// else may print to it. There is no approver, so high risk tools need `serve_high_risk`.
+async fn serve_mcp(args: &Args, serve_high_risk: bool) -> Result<()> {
+    let http = HttpContext::new(HttpConfig::default())?;
+    let mut server = McpServer::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let skipped = server.add_policy_tools(agent_tools(args, http).await?, serve_high_risk);
+    if !skipped.is_empty() {
+        eprintln!(
+            "Not serving the high risk tools {}, pass --serve-high-risk to serve them without approval",
+            skipped.join(", ")
+        );
+    }
+
+    eprintln!("Serving MCP tools over stdio");
+    server.serve_stdio().await
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
@@ -70,6 +341,10 @@ async fn main() -> Result<()> {
         }
     }
 
+    if let Some(Command::McpServe { serve_high_risk }) = args.command {
+        return serve_mcp(&args, serve_high_risk).await;
+    }
+
     println!(
         "\nOPENAI_API_KEY: {}",
         env::var("OPENAI_API_KEY").unwrap().bold().bright_green()
@@ -85,9 +360,25 @@ async fn main() -> Result<()> {
         env::var("LLM_MODEL").unwrap().bold().bright_green()
     );
 
-    let query = format!("What is the weather like in {} today?", args.location);
+    let template = match args.prompt_template {
+        Some(ref path) => Some(PromptTemplate::from_file(path)?),
+        None => None,
+    };
+    let options = PromptOptions {
+        template,
+        renderer: args.tool_format,
+        locale: args.lang,
+        example: None,
+    };
 
-    let base_agent = crate_base_agent().await?;
+    let http = HttpContext::new(HttpConfig::default())?;
+
+    let tools = agent_tools(&args, http).await?;
+    let definitions: Vec<ToolDefinition> = tools
+        .iter()
+        .map(|(definition, _, _)| definition.clone())
+        .collect();
+    let base_agent = crate_base_agent(&options, &definitions).await?;
     let mut react_agent = ReactAgent::new(
         "React Agent".to_string(),
         "An agent that can react to user queries and use tools".to_string(),
@@ -95,16 +386,48 @@ async fn main() -> Result<()> {
         Some(10), // Set max interactions to 10
     );
 
-    react_agent.add_tool("get_weather", GetWeatherTool);
-    react_agent.add_tool("get_geo_location", GetGeoLocationTool);
+    // Ctrl-C stops the run instead of killing the process mid tool call
+    let cancel = CancellationToken::new();
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            trigger.cancel();
+        }
+    });
 
-    match react_agent.react_loop(&query).await {
-        Ok(answer) => {
+    react_agent.set_approver(CliApprover {
+        cancel: cancel.clone(),
+    });
+    if let Some(ref dir) = args.checkpoint_dir {
+        react_agent.set_checkpoint_store(FileCheckpointStore::new(dir));
+    }
+
+    for (definition, function, policy) in tools {
+        react_agent.add_tool_with_policy(&definition.name, function, policy);
+    }
+
+    let outcome = match (args.resume, args.location) {
+        (Some(run_id), _) => react_agent.resume_with_cancel(&run_id, cancel).await,
+        (None, Some(location)) => {
+            let query = format!("What is the weather like in {} today?", location);
+            react_agent.react_loop_with_cancel(&query, cancel).await
+        }
+        (None, None) => unreachable!("clap requires a location unless resuming"),
+    };
+
+    match outcome {
+        Ok(ReactOutcome::Answer(answer)) => {
             println!("\n\n");
             println!("Final answer: ");
             println!("{}", "--".repeat(30).bold().bright_green());
             println!("{}", answer.bold().bright_green());
         }
+        Ok(ReactOutcome::Cancelled(transcript)) => {
+            println!(
+                "\n{}",
+                format!("Cancelled after {} messages.", transcript.len()).yellow()
+            );
+        }
         Err(e) => {
             println!("Error: {}", e.to_string().red());
         }
@@ -0,0 +1,378 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::agent::tool::{ToolDefinition, ToolFunction};
+use crate::error::AgentError;
+use crate::http::HttpContext;
+use crate::prelude::*;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{
+    PROTOCOL_VERSION,
+    transport::{HttpTransport, McpTransport, StdioTransport},
+};
+
+/// Time allowed to an MCP server to answer a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A tool listed by an MCP server
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON schema of the arguments
+    pub input_schema: Value,
+}
+
+impl McpTool {
+    /// The tool as declared to the model in the system prompt
+    pub fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone().unwrap_or_default(),
+            parameters: self.input_schema.clone(),
+            example: None,
+        }
+    }
+}
+
+/// Client side of an MCP session, initialized on connect
+pub struct McpClient {
+    transport: Box<dyn McpTransport>,
+    next_id: AtomicU64,
+    server_info: Value,
+}
+
+impl McpClient {
+    pub async fn connect(transport: impl McpTransport + 'static) -> Result<Self> {
+        let mut client = McpClient {
+            transport: Box::new(transport),
+            next_id: AtomicU64::new(1),
+            server_info: Value::Null,
+        };
+
+        let initialized = client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION")
+                    }
+                }),
+            )
+            .await?;
+        client.server_info = initialized.get("serverInfo").cloned().unwrap_or_default();
+
+        client
+            .transport
+            .notify(json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+            .await?;
+
+        Ok(client)
+    }
+
+    /// Start a server command and connect to it over stdio
+    pub async fn stdio(command: &str, args: &[String]) -> Result<Self> {
+        McpClient::connect(StdioTransport::spawn(command, args)?).await
+    }
+
+    /// Connect to a server over streamable HTTP
+    pub async fn http(http: HttpContext, url: &str) -> Result<Self> {
+        McpClient::connect(HttpTransport::new(http, url)).await
+    }
+
+    /// Name and version reported by the server
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, self.transport.request(request))
+            .await
+            .map_err(|_| AgentError::Timeout(format!("MCP {}", method), REQUEST_TIMEOUT))??;
+
+        if let Some(error) = response.get("error") {
+            return Err(AgentError::Generic(format!(
+                "MCP {} failed: {} ({})",
+                method,
+                error.get("message").and_then(Value::as_str).unwrap_or(""),
+                error.get("code").unwrap_or(&Value::Null)
+            )));
+        }
+        Ok(response.get("result").cloned().unwrap_or_default())
+    }
+
+    /// All tools of the server, following pagination
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params).await?;
+
+            let page: Vec<McpTool> =
+                serde_json::from_value(result.get("tools").cloned().unwrap_or(json!([])))
+                    .map_err(|e| AgentError::Generic(format!("Invalid MCP tool list: {}", e)))?;
+            tools.extend(page);
+
+            cursor = result
+                .get("nextCursor")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            if cursor.is_none() {
+                return Ok(tools);
+            }
+        }
+    }
+
+    /// Call a tool, its structured content or the content of the result is returned,
+    /// results flagged as errors are returned as errors.
+    pub async fn call_tool(&self, name: &str, args: Value) -> Result<Value> {
+        let arguments = if args.is_null() { json!({}) } else { args };
+        let result = self
+            .request("tools/call", json!({"name": name, "arguments": arguments}))
+            .await?;
+
+        let content = content_value(&result);
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            let message = match content {
+                Value::String(text) => text,
+                other => other.to_string(),
+            };
+            return Err(AgentError::Generic(message));
+        }
+
+        Ok(result.get("structuredContent").cloned().unwrap_or(content))
+    }
+
+    /// Definitions of all tools of the server with adapters to call them
+    pub async fn tools(self: &Arc<Self>) -> Result<Vec<(ToolDefinition, McpToolFunction)>> {
+        Ok(self
+            .list_tools()
+            .await?
+            .into_iter()
+            .map(|tool| {
+                let function = McpToolFunction::new(self.clone(), &tool.name);
+                (tool.definition(), function)
+            })
+            .collect())
+    }
+}
+
+/// Text items of the content as strings, parsed as JSON when possible,
+/// other items are kept as is.
+fn content_value(result: &Value) -> Value {
+    let mut items: Vec<Value> = result
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|content| {
+            content
+                .iter()
+                .map(|item| match item.get("text").and_then(Value::as_str) {
+                    Some(text) => serde_json::from_str(text)
+                        .unwrap_or_else(|_| Value::String(text.to_string())),
+                    None => item.clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    match items.len() {
+        0 => Value::Null,
+        1 => items.remove(0),
+        _ => Value::Array(items),
+    }
+}
+
+/// A tool of an MCP server, called through the server's session
+pub struct McpToolFunction {
+    client: Arc<McpClient>,
+    name: String,
+}
+
+impl McpToolFunction {
+    pub fn new(client: Arc<McpClient>, name: &str) -> Self {
+        McpToolFunction {
+            client,
+            name: name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ToolFunction for McpToolFunction {
+    async fn call(&self, args: Value) -> Result<Value> {
+        self.client.call_tool(&self.name, args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_partial_json, method},
+    };
+
+    /// A small stdio MCP server with an `echo` and an `add` tool, listed on two pages
+    async fn fixture_server(stream: tokio::io::DuplexStream) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let Some(id) = request.get("id").cloned() else {
+                continue;
+            };
+            let params = &request["params"];
+
+            let result = match request["method"].as_str().unwrap() {
+                "initialize" => json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "fixture", "version": "1.0.0" }
+                }),
+                "tools/list" if params.get("cursor").is_none() => json!({
+                    "tools": [{
+                        "name": "echo",
+                        "description": "Echo the arguments",
+                        "inputSchema": { "type": "object", "properties": {} }
+                    }],
+                    "nextCursor": "page-2"
+                }),
+                "tools/list" => json!({
+                    "tools": [{
+                        "name": "add",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": { "a": { "type": "number" }, "b": { "type": "number" } },
+                            "required": ["a", "b"]
+                        }
+                    }]
+                }),
+                "tools/call" => match params["name"].as_str().unwrap() {
+                    "echo" => json!({
+                        "content": [{ "type": "text", "text": params["arguments"].to_string() }]
+                    }),
+                    "add" => {
+                        let sum = params["arguments"]["a"].as_f64().unwrap()
+                            + params["arguments"]["b"].as_f64().unwrap();
+                        json!({ "content": [{ "type": "text", "text": sum.to_string() }] })
+                    }
+                    other => json!({
+                        "content": [{ "type": "text", "text": format!("Unknown tool: {}", other) }],
+                        "isError": true
+                    }),
+                },
+                _ => {
+                    let error = json!({"jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": "Method not found" }});
+                    writer
+                        .write_all(format!("{}\n", error).as_bytes())
+                        .await
+                        .unwrap();
+                    continue;
+                }
+            };
+
+            let response = json!({"jsonrpc": "2.0", "id": id, "result": result});
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn fixture_client() -> Arc<McpClient> {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        tokio::spawn(fixture_server(server_io));
+        let (reader, writer) = tokio::io::split(client_io);
+        Arc::new(
+            McpClient::connect(StdioTransport::new(reader, writer))
+                .await
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn list_and_call_stdio_tools() {
+        let client = fixture_client().await;
+        assert_eq!(client.server_info()["name"], "fixture");
+
+        let tools = client.tools().await.unwrap();
+        let names: Vec<&str> = tools.iter().map(|(d, _)| d.name.as_str()).collect();
+        assert_eq!(names, ["echo", "add"]);
+        assert_eq!(tools[1].0.parameters["required"], json!(["a", "b"]));
+
+        let (results, echoed) = tokio::join!(
+            tools[1].1.call(json!({"a": 1, "b": 2})),
+            tools[0].1.call(json!({"text": "hi"}))
+        );
+        assert_eq!(results.unwrap(), json!(3));
+        assert_eq!(echoed.unwrap(), json!({"text": "hi"}));
+
+        let unknown = client.call_tool("missing", json!({})).await;
+        assert!(matches!(unknown, Err(AgentError::Generic(m)) if m == "Unknown tool: missing"));
+    }
+
+    #[tokio::test]
+    async fn call_http_tool_with_sse_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"method": "initialize"})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Mcp-Session-Id", "session-1")
+                    .set_body_json(json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "result": { "protocolVersion": PROTOCOL_VERSION, "capabilities": {}, "serverInfo": { "name": "remote" } }
+                    })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(
+                json!({"method": "notifications/initialized"}),
+            ))
+            .respond_with(ResponseTemplate::new(202))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(json!({"method": "tools/call"})))
+            .and(wiremock::matchers::header("Mcp-Session-Id", "session-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                "event: message\ndata: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"content\":[],\"structuredContent\":{\"ok\":true}}}\n\n",
+                "text/event-stream",
+            ))
+            .mount(&server)
+            .await;
+
+        let client = McpClient::http(HttpContext::default(), &server.uri())
+            .await
+            .unwrap();
+        assert_eq!(client.server_info()["name"], "remote");
+        assert_eq!(
+            client.call_tool("status", Value::Null).await.unwrap(),
+            json!({"ok": true})
+        );
+    }
+}
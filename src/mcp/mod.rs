@@ -0,0 +1,9 @@
+//! Model Context Protocol support: JSON-RPC 2.0 messages exchanged as one JSON
+//! object per line over stdio, or POSTed over HTTP with JSON or SSE responses.
+
+pub mod client;
+pub mod server;
+pub mod transport;
+
+/// MCP revision spoken by this crate
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
@@ -0,0 +1,308 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::agent::{
+    approval::RiskLevel,
+    policy::{GuardedTool, PolicyTool},
+    tool::{FunctionSchemaStyle, ToolDefinition, ToolFunction, build_function_schema},
+};
+use crate::error::AgentError;
+use crate::prelude::*;
+use schemars::JsonSchema;
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::Mutex,
+};
+
+use super::PROTOCOL_VERSION;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// MCP server exposing tools over newline-delimited JSON-RPC. Tool calls run
+/// concurrently; tools must log to stderr when served over stdio.
+pub struct McpServer {
+    name: String,
+    version: String,
+    tools: Vec<ToolDefinition>,
+    functions: HashMap<String, Arc<dyn ToolFunction>>,
+}
+
+impl McpServer {
+    pub fn new(name: &str, version: &str) -> Self {
+        McpServer {
+            name: name.to_string(),
+            version: version.to_string(),
+            tools: Vec::new(),
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Expose a tool taking arguments of type `A`
+    pub fn add_tool<A: JsonSchema, F: ToolFunction + 'static>(
+        &mut self,
+        name: &str,
+        description: &str,
+        tool: F,
+    ) {
+        let schema = build_function_schema::<A>(name, description, FunctionSchemaStyle::Legacy);
+        let definition =
+            ToolDefinition::from_schema(&schema).expect("A generated function schema is complete");
+        self.add_tool_with_definition(definition, Arc::new(tool));
+    }
+
+    pub fn add_tool_with_definition(
+        &mut self,
+        definition: ToolDefinition,
+        tool: Arc<dyn ToolFunction>,
+    ) {
+        self.tools.retain(|t| t.name != definition.name);
+        self.functions.insert(definition.name.clone(), tool);
+        self.tools.push(definition);
+    }
+
+    /// Expose tools guarded by their policies. Calls are not approved over MCP, so high risk
+    /// tools are skipped unless `allow_high_risk` is set. Returns the names of skipped tools.
+    pub fn add_policy_tools(
+        &mut self,
+        tools: Vec<PolicyTool>,
+        allow_high_risk: bool,
+    ) -> Vec<String> {
+        let mut skipped = Vec::new();
+        for (definition, function, policy) in tools {
+            if policy.risk >= RiskLevel::High && !allow_high_risk {
+                skipped.push(definition.name);
+                continue;
+            }
+            let tool = GuardedTool::new(&definition.name, function, policy);
+            self.add_tool_with_definition(definition, Arc::new(tool));
+        }
+        skipped
+    }
+
+    pub async fn serve_stdio(self) -> Result<()> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serve requests read from `reader` until it is closed
+    pub async fn serve(
+        self,
+        reader: impl AsyncRead + Unpin,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Result<()> {
+        let server = Arc::new(self);
+        let writer = Arc::new(Mutex::new(writer));
+        let mut lines = BufReader::new(reader).lines();
+        let mut calls = tokio::task::JoinSet::new();
+
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| AgentError::Generic(format!("Failed to read MCP request: {}", e)))?
+        {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let server = server.clone();
+            let writer = writer.clone();
+            calls.spawn(async move {
+                if let Some(response) = server.handle_line(&line).await {
+                    let mut writer = writer.lock().await;
+                    let written = match writer.write_all(format!("{}\n", response).as_bytes()).await
+                    {
+                        Ok(()) => writer.flush().await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = written {
+                        eprintln!("Failed to write MCP response: {}", e);
+                    }
+                }
+            });
+        }
+
+        // answer the calls still running before the stream is closed
+        while calls.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    async fn handle_line(&self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+
+        // notifications get no response
+        let id = request.get("id")?.clone();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Some(error_response(id, INVALID_REQUEST, "Missing method"));
+        };
+        let params = request.get("params").cloned().unwrap_or_default();
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": self.name, "version": self.version }
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.list_tools() })),
+            "tools/call" => self.call_tool(&params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn list_tools(&self) -> Vec<Value> {
+        self.tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "inputSchema": tool.parameters
+                })
+            })
+            .collect()
+    }
+
+    /// Failures of the tool are returned as results flagged with `isError`,
+    /// so the calling model can see them.
+    async fn call_tool(&self, params: &Value) -> std::result::Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+        let tool = self
+            .functions
+            .get(name)
+            .ok_or((INVALID_PARAMS, format!("Unknown tool: {}", name)))?;
+        let args = params.get("arguments").cloned().unwrap_or(json!({}));
+
+        Ok(match tool.call(args).await {
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+                "isError": false
+            }),
+            Err(e) => json!({
+                "content": [{ "type": "text", "text": e.to_string() }],
+                "isError": true
+            }),
+        })
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message }})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::{client::McpClient, transport::StdioTransport};
+    use async_trait::async_trait;
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct ShoutArgs {
+        /// text to shout
+        text: String,
+    }
+
+    struct ShoutTool;
+
+    #[async_trait]
+    impl ToolFunction for ShoutTool {
+        async fn call(&self, args: Value) -> Result<Value> {
+            match args.get("text").and_then(Value::as_str) {
+                Some(text) => Ok(Value::from(text.to_uppercase())),
+                None => Err(AgentError::Generic("text is required".to_string())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_tools_to_client() {
+        let mut server = McpServer::new("test", "0.1.0");
+        server.add_tool::<ShoutArgs, _>("shout", "Shout the text", ShoutTool);
+
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let (server_reader, server_writer) = tokio::io::split(server_io);
+        tokio::spawn(server.serve(server_reader, server_writer));
+
+        let (reader, writer) = tokio::io::split(client_io);
+        let client = McpClient::connect(StdioTransport::new(reader, writer))
+            .await
+            .unwrap();
+        assert_eq!(client.server_info()["name"], "test");
+
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].input_schema["required"], json!(["text"]));
+
+        assert_eq!(
+            client
+                .call_tool("shout", json!({"text": "hi"}))
+                .await
+                .unwrap(),
+            json!("HI")
+        );
+        assert!(client.call_tool("shout", json!({})).await.is_err());
+        assert!(client.call_tool("whisper", json!({})).await.is_err());
+    }
+
+    #[test]
+    fn skip_high_risk_tools() {
+        use crate::tools::{
+            fs::{FsConfig, fs_tools},
+            shell::{RunShellTool, ShellConfig},
+        };
+
+        let tools = || {
+            let dir = std::env::temp_dir();
+            let mut tools = fs_tools(FsConfig::new(&dir)).unwrap();
+            tools.push((
+                ToolDefinition::new::<crate::tools::shell::RunShellArgs>("run_shell", "Run"),
+                Arc::new(RunShellTool::new(ShellConfig::new(&dir, &["ls"]))),
+                RunShellTool::policy(),
+            ));
+            tools
+        };
+        let listed = |server: &McpServer| -> Vec<String> {
+            server.tools.iter().map(|tool| tool.name.clone()).collect()
+        };
+
+        let mut server = McpServer::new("test", "0.1.0");
+        let skipped = server.add_policy_tools(tools(), false);
+        assert_eq!(skipped, ["write_file", "run_shell"]);
+        assert_eq!(listed(&server), ["read_file", "list_dir", "search_files"]);
+
+        let mut server = McpServer::new("test", "0.1.0");
+        assert!(server.add_policy_tools(tools(), true).is_empty());
+        assert!(listed(&server).contains(&"run_shell".to_string()));
+    }
+
+    #[tokio::test]
+    async fn reject_malformed_requests() {
+        let server = McpServer::new("test", "0.1.0");
+
+        let parse_error = server.handle_line("{").await.unwrap();
+        assert_eq!(parse_error["error"]["code"], PARSE_ERROR);
+
+        let unknown = server
+            .handle_line(r#"{"jsonrpc":"2.0","id":7,"method":"resources/list"}"#)
+            .await
+            .unwrap();
+        assert_eq!(unknown["id"], 7);
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        assert!(server.handle_line(notification).await.is_none());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{Arc, Mutex},
+};
+
+use crate::error::AgentError;
+use crate::http::HttpContext;
+use crate::prelude::*;
+use async_trait::async_trait;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+/// Channel to an MCP server carrying JSON-RPC messages
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// Send a request and wait for the response with the same id
+    async fn request(&self, request: Value) -> Result<Value>;
+    /// Send a notification, the server does not respond to it
+    async fn notify(&self, notification: Value) -> Result<()>;
+}
+
+type Pending = Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>;
+
+/// Newline-delimited JSON over a pair of streams, usually the stdio of a child process.
+/// Responses are matched to requests by id, so concurrent calls do not wait for each other.
+pub struct StdioTransport {
+    writer: tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>,
+    pending: Pending,
+    reader: JoinHandle<()>,
+    // killed when the transport is dropped
+    _child: Option<Child>,
+}
+
+impl StdioTransport {
+    pub fn new(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+
+        let routes = pending.clone();
+        let reader = tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                // server requests and notifications are not supported and skipped
+                if message.get("result").is_none() && message.get("error").is_none() {
+                    continue;
+                }
+                let Some(id) = message.get("id") else {
+                    continue;
+                };
+                if let Some(respond) = routes.lock().unwrap().remove(&id.to_string()) {
+                    let _ = respond.send(message);
+                }
+            }
+            // the server is gone, fail the calls still waiting
+            routes.lock().unwrap().clear();
+        });
+
+        StdioTransport {
+            writer: tokio::sync::Mutex::new(Box::new(writer)),
+            pending,
+            reader,
+            _child: None,
+        }
+    }
+
+    /// Start `command` and talk to it over its stdin and stdout, its stderr is inherited
+    pub fn spawn(command: &str, args: &[String]) -> Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                AgentError::Generic(format!("Failed to start MCP server {}: {}", command, e))
+            })?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut transport = StdioTransport::new(stdout, stdin);
+        transport._child = Some(child);
+        Ok(transport)
+    }
+
+    async fn send(&self, message: &Value) -> Result<()> {
+        let mut line = message.to_string();
+        line.push('\n');
+
+        let mut writer = self.writer.lock().await;
+        let written = match writer.write_all(line.as_bytes()).await {
+            Ok(()) => writer.flush().await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| AgentError::Generic(format!("Failed to write to MCP server: {}", e)))
+    }
+}
+
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Drop for StdioTransport {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn request(&self, request: Value) -> Result<Value> {
+        let id = request
+            .get("id")
+            .ok_or_else(|| AgentError::Generic("MCP request has no id".to_string()))?
+            .to_string();
+
+        let (respond, response) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), respond);
+        // removes the entry when the request fails or is dropped, e.g. on a timeout
+        let _pending = PendingGuard {
+            pending: &self.pending,
+            id,
+        };
+
+        self.send(&request).await?;
+        response
+            .await
+            .map_err(|_| AgentError::Generic("MCP server closed the connection".to_string()))
+    }
+
+    async fn notify(&self, notification: Value) -> Result<()> {
+        self.send(&notification).await
+    }
+}
+
+/// Streamable HTTP: every message is POSTed to one endpoint, the server answers
+/// with a JSON body or an SSE stream carrying the response.
+pub struct HttpTransport {
+    http: HttpContext,
+    url: String,
+    session_id: Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    pub fn new(http: HttpContext, url: &str) -> Self {
+        HttpTransport {
+            http,
+            url: url.to_string(),
+            session_id: Mutex::new(None),
+        }
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response> {
+        let mut request = self
+            .http
+            .client()
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ACCEPT, "application/json, text/event-stream")
+            .body(message.to_string());
+        let session_id = self.session_id.lock().unwrap().clone();
+        if let Some(session_id) = session_id {
+            request = request.header("Mcp-Session-Id", session_id);
+        }
+
+        let response = request.send().await?;
+        if let Some(session_id) = response
+            .headers()
+            .get("Mcp-Session-Id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session_id.lock().unwrap() = Some(session_id.to_string());
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AgentError::Generic(format!(
+                "MCP server {} returned {}: {}",
+                self.url, status, body
+            )));
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn request(&self, request: Value) -> Result<Value> {
+        let response = self.post(&request).await?;
+        let is_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        let body = response.text().await?;
+
+        let id = request.get("id");
+        if is_stream {
+            return sse_messages(&body)
+                .into_iter()
+                .find(|message| message.get("id") == id)
+                .ok_or_else(|| {
+                    AgentError::Generic(format!("MCP server {} sent no response", self.url))
+                });
+        }
+
+        serde_json::from_str(&body).map_err(|e| {
+            AgentError::Generic(format!(
+                "Invalid response from MCP server {}: {}",
+                self.url, e
+            ))
+        })
+    }
+
+    async fn notify(&self, notification: Value) -> Result<()> {
+        self.post(&notification).await.map(|_| ())
+    }
+}
+
+/// JSON messages in the `data` fields of an SSE stream
+fn sse_messages(body: &str) -> Vec<Value> {
+    body.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter_map(|event| {
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect::<Vec<&str>>()
+                .join("\n");
+            serde_json::from_str(&data).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_sse_messages() {
+        let body = "event: message\r\ndata: {\"jsonrpc\":\"2.0\",\r\ndata: \"id\":1}\r\n\r\n: ping\n\ndata: [1]\n\n";
+        assert_eq!(
+            sse_messages(body),
+            vec![json!({"jsonrpc": "2.0", "id": 1}), json!([1])]
+        );
+    }
+
+    #[tokio::test]
+    async fn forget_timed_out_requests() {
+        // the server side is kept open but never answers
+        let (client_io, _server_io) = tokio::io::duplex(4096);
+        let (reader, writer) = tokio::io::split(client_io);
+        let transport = StdioTransport::new(reader, writer);
+
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "ping"});
+        let timed_out = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            transport.request(request),
+        )
+        .await;
+        assert!(timed_out.is_err());
+        assert!(transport.pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,655 @@
+use std::path::Path;
+
+use crate::agent::tool::{ToolDefinition, ToolFunction};
+use crate::error::AgentError;
+use crate::http::HttpContext;
+use crate::prelude::*;
+use async_trait::async_trait;
+use colored::Colorize;
+use reqwest::Method;
+use serde_json::{Map, Value, json};
+
+/// Depth at which nested `$ref`s stop being inlined, guards against recursive schemas
+const MAX_REF_DEPTH: usize = 8;
+
+/// An OpenAPI 3 document, each of its operations can be turned into a tool
+#[derive(Debug, Clone)]
+pub struct OpenApiSpec {
+    document: Value,
+    base_url: Option<String>,
+    /// Parameters hidden from the model and sent by the operations declaring them, e.g. API keys
+    fixed_parameters: Vec<(String, String)>,
+}
+
+impl OpenApiSpec {
+    /// Parse a document in JSON or YAML
+    pub fn parse(source: &str) -> Result<Self> {
+        let document: Value = match serde_json::from_str(source) {
+            Ok(document) => document,
+            Err(_) => serde_yaml::from_str(source)
+                .map_err(|e| AgentError::Generic(format!("Invalid OpenAPI document: {}", e)))?,
+        };
+
+        let version = document
+            .get("openapi")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if !version.starts_with("3.") {
+            return Err(AgentError::Generic(format!(
+                "Unsupported OpenAPI version: {:?}, expected 3.x",
+                version
+            )));
+        }
+
+        let base_url = document
+            .pointer("/servers/0")
+            .and_then(server_url)
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"));
+
+        Ok(OpenApiSpec {
+            document,
+            base_url,
+            fixed_parameters: Vec::new(),
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            AgentError::Generic(format!(
+                "Failed to read OpenAPI document {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        OpenApiSpec::parse(&source)
+    }
+
+    /// Override the first server of the document, required when it is relative or missing
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_string());
+        self
+    }
+
+    /// Send `value` for the parameter `name` to every operation declaring it, in its declared
+    /// location, and leave it out of the tool schemas
+    pub fn with_fixed_parameter(mut self, name: &str, value: &str) -> Self {
+        self.fixed_parameters
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// One tool per operation, ordered by path then method. Operations that cannot be
+    /// called, e.g. with a cookie parameter, are skipped with a warning.
+    pub fn tools(&self, http: &HttpContext) -> Result<Vec<(ToolDefinition, OpenApiTool)>> {
+        let base_url = self.base_url.clone().ok_or_else(|| {
+            AgentError::Generic(
+                "OpenAPI document has no absolute server url, set a base url".to_string(),
+            )
+        })?;
+
+        let mut tools = Vec::new();
+        let Some(paths) = self.document.get("paths").and_then(Value::as_object) else {
+            return Ok(tools);
+        };
+
+        for (path, item) in paths {
+            let item = self.resolve(item, 0);
+            let shared_parameters = item.get("parameters").cloned().unwrap_or(json!([]));
+
+            for method in ["get", "put", "post", "delete", "patch", "head", "options"] {
+                let Some(operation) = item.get(method) else {
+                    continue;
+                };
+                match self.operation_tool(
+                    http,
+                    &base_url,
+                    method,
+                    path,
+                    operation,
+                    &shared_parameters,
+                ) {
+                    Ok(tool) => tools.push(tool),
+                    Err(e) => eprintln!(
+                        "{}",
+                        format!("Skipping {} {}: {}", method.to_uppercase(), path, e).yellow()
+                    ),
+                }
+            }
+        }
+
+        Ok(tools)
+    }
+
+    fn operation_tool(
+        &self,
+        http: &HttpContext,
+        base_url: &str,
+        method: &str,
+        path: &str,
+        operation: &Value,
+        shared_parameters: &Value,
+    ) -> Result<(ToolDefinition, OpenApiTool)> {
+        let name = operation
+            .get("operationId")
+            .and_then(Value::as_str)
+            .map(tool_name)
+            .unwrap_or_else(|| tool_name(&format!("{}_{}", method, path)));
+
+        let description = ["summary", "description"]
+            .iter()
+            .find_map(|key| operation.get(*key).and_then(Value::as_str))
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{} {}", method.to_uppercase(), path));
+
+        // operation parameters override the path item ones with the same name and location
+        let mut parameters: Vec<ParameterSpec> = Vec::new();
+        for parameter in shared_parameters
+            .as_array()
+            .into_iter()
+            .chain(operation.get("parameters").and_then(Value::as_array))
+            .flatten()
+        {
+            let parameter = ParameterSpec::parse(&self.resolve(parameter, 0), &name)?;
+            parameters.retain(|p| !(p.name == parameter.name && p.location == parameter.location));
+            parameters.push(parameter);
+        }
+
+        let mut fixed_parameters = Vec::new();
+        parameters.retain(|parameter| {
+            match self
+                .fixed_parameters
+                .iter()
+                .find(|(n, _)| *n == parameter.name)
+            {
+                Some((_, value)) => {
+                    fixed_parameters.push((parameter.clone(), value.clone()));
+                    false
+                }
+                None => true,
+            }
+        });
+
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for parameter in &parameters {
+            let mut schema = self.resolve(&parameter.schema, 0);
+            if let (Some(description), Some(object)) =
+                (&parameter.description, schema.as_object_mut())
+            {
+                object.insert("description".to_string(), json!(description));
+            }
+            properties.insert(parameter.name.clone(), schema);
+            if parameter.required {
+                required.push(json!(parameter.name));
+            }
+        }
+
+        let body = operation
+            .get("requestBody")
+            .map(|body| self.resolve(body, 0));
+        let body_schema = body
+            .as_ref()
+            .and_then(|body| body.pointer("/content/application~1json/schema"));
+        if let Some(schema) = body_schema {
+            let mut schema = self.resolve(schema, 0);
+            if let (Some(description), Some(object)) = (
+                body.as_ref()
+                    .and_then(|b| b.get("description"))
+                    .and_then(Value::as_str),
+                schema.as_object_mut(),
+            ) {
+                object
+                    .entry("description")
+                    .or_insert_with(|| json!(description));
+            }
+            properties.insert("body".to_string(), schema);
+            if body
+                .as_ref()
+                .and_then(|b| b.get("required"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+            {
+                required.push(json!("body"));
+            }
+        }
+
+        let definition = ToolDefinition {
+            name,
+            description,
+            parameters: json!({
+                "type": "object",
+                "properties": properties,
+                "required": required
+            }),
+            example: None,
+        };
+
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .expect("Only standard methods are imported");
+        let tool = OpenApiTool {
+            http: http.clone(),
+            method,
+            url: format!("{}{}", base_url.trim_end_matches('/'), path),
+            parameters,
+            has_body: body_schema.is_some(),
+            fixed_parameters,
+        };
+
+        Ok((definition, tool))
+    }
+
+    /// Inline `#/...` references of the document
+    fn resolve(&self, value: &Value, depth: usize) -> Value {
+        match value {
+            Value::Object(object) => {
+                if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                    let target = reference
+                        .strip_prefix('#')
+                        .and_then(|pointer| self.document.pointer(pointer));
+                    return match target {
+                        Some(target) if depth < MAX_REF_DEPTH => self.resolve(target, depth + 1),
+                        _ => json!({}),
+                    };
+                }
+                Value::Object(
+                    object
+                        .iter()
+                        .map(|(key, value)| (key.clone(), self.resolve(value, depth)))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|item| self.resolve(item, depth)).collect())
+            }
+            other => other.clone(),
+        }
+    }
+}
+
+/// Url of a server object with its variables replaced by their defaults
+fn server_url(server: &Value) -> Option<String> {
+    let mut url = server.get("url")?.as_str()?.to_string();
+    if let Some(variables) = server.get("variables").and_then(Value::as_object) {
+        for (name, variable) in variables {
+            if let Some(default) = variable.get("default").and_then(Value::as_str) {
+                url = url.replace(&format!("{{{}}}", name), default);
+            }
+        }
+    }
+    Some(url)
+}
+
+/// Tool names are limited to letters, digits, `_` and `-` by most model APIs
+fn tool_name(raw: &str) -> String {
+    let name: String = raw
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('_').replace("__", "_");
+    name.chars().take(64).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParameterLocation {
+    Path,
+    Query,
+    Header,
+}
+
+#[derive(Debug, Clone)]
+struct ParameterSpec {
+    name: String,
+    location: ParameterLocation,
+    required: bool,
+    description: Option<String>,
+    schema: Value,
+}
+
+impl ParameterSpec {
+    fn parse(parameter: &Value, operation: &str) -> Result<Self> {
+        let name = parameter
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                AgentError::Generic(format!("Parameter of {} has no name", operation))
+            })?;
+        let location = match parameter.get("in").and_then(Value::as_str) {
+            Some("path") => ParameterLocation::Path,
+            Some("query") => ParameterLocation::Query,
+            Some("header") => ParameterLocation::Header,
+            other => {
+                return Err(AgentError::Generic(format!(
+                    "Parameter {} of {} is in unsupported location {:?}",
+                    name, operation, other
+                )));
+            }
+        };
+
+        Ok(ParameterSpec {
+            name: name.to_string(),
+            location,
+            // path parameters are always required
+            required: location == ParameterLocation::Path
+                || parameter
+                    .get("required")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+            description: parameter
+                .get("description")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            schema: parameter
+                .get("schema")
+                .cloned()
+                .unwrap_or(json!({ "type": "string" })),
+        })
+    }
+}
+
+/// An OpenAPI operation called with the shared HTTP client
+pub struct OpenApiTool {
+    http: HttpContext,
+    method: Method,
+    /// Url with `{name}` placeholders of the path parameters
+    url: String,
+    parameters: Vec<ParameterSpec>,
+    has_body: bool,
+    /// Declared parameters with a fixed value, they are not in the schema
+    fixed_parameters: Vec<(ParameterSpec, String)>,
+}
+
+/// Parameter value as sent in a url or header, strings without quotes
+fn parameter_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Percent-encode a path segment, keeping the unreserved characters of RFC 3986
+fn encode_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl ToolFunction for OpenApiTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let mut url = self.url.clone();
+        let mut query: Vec<(String, String)> = Vec::new();
+        let mut headers: Vec<(String, String)> = Vec::new();
+
+        let mut values: Vec<(&ParameterSpec, Value)> = Vec::new();
+        for parameter in &self.parameters {
+            match args.get(&parameter.name) {
+                Some(value) if !value.is_null() => values.push((parameter, value.clone())),
+                _ if parameter.required => {
+                    return Err(AgentError::Generic(format!(
+                        "Missing required parameter: {}",
+                        parameter.name
+                    )));
+                }
+                _ => {}
+            }
+        }
+        values.extend(
+            self.fixed_parameters
+                .iter()
+                .map(|(parameter, value)| (parameter, json!(value))),
+        );
+
+        for (parameter, value) in values {
+            match parameter.location {
+                ParameterLocation::Path => {
+                    url = url.replace(
+                        &format!("{{{}}}", parameter.name),
+                        &encode_segment(&parameter_string(&value)),
+                    );
+                }
+                // arrays are exploded into repeated parameters, the OpenAPI default
+                ParameterLocation::Query => match value {
+                    Value::Array(items) => query.extend(
+                        items
+                            .iter()
+                            .map(|item| (parameter.name.clone(), parameter_string(item))),
+                    ),
+                    _ => query.push((parameter.name.clone(), parameter_string(&value))),
+                },
+                ParameterLocation::Header => {
+                    headers.push((parameter.name.clone(), parameter_string(&value)))
+                }
+            }
+        }
+
+        let mut request = self
+            .http
+            .client()
+            .request(self.method.clone(), &url)
+            .query(&query);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if self.has_body
+            && let Some(body) = args.get("body").filter(|body| !body.is_null())
+        {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(AgentError::Generic(format!(
+                "{} {} returned {}: {}",
+                self.method, url, status, body
+            )));
+        }
+
+        Ok(serde_json::from_str(&body).unwrap_or(Value::String(body)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_json, header, method, path, query_param, query_param_is_missing},
+    };
+
+    const PETSTORE: &str = r#"
+openapi: 3.0.3
+info:
+  title: Pet store
+  version: 1.0.0
+servers:
+  - url: https://{region}.pets.example.com/v1
+    variables:
+      region:
+        default: eu
+paths:
+  /pets/{petId}:
+    parameters:
+      - name: petId
+        in: path
+        description: id of the pet
+        schema:
+          type: integer
+    get:
+      operationId: getPet
+      summary: Get a pet by id
+      parameters:
+        - name: fields
+          in: query
+          schema:
+            type: array
+            items:
+              type: string
+        - name: api_key
+          in: query
+          required: true
+          schema:
+            type: string
+  /pets:
+    post:
+      summary: Add a pet
+      parameters:
+        - name: X-Trace-Id
+          in: header
+          schema:
+            type: string
+        - name: X-Api-Key
+          in: header
+          required: true
+          schema:
+            type: string
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/Pet'
+components:
+  schemas:
+    Pet:
+      type: object
+      required: [name]
+      properties:
+        name:
+          type: string
+        tags:
+          type: array
+          items:
+            $ref: '#/components/schemas/Tag'
+    Tag:
+      type: string
+"#;
+
+    #[test]
+    fn import_operations() {
+        let spec = OpenApiSpec::parse(PETSTORE)
+            .unwrap()
+            .with_fixed_parameter("api_key", "secret")
+            .with_fixed_parameter("X-Api-Key", "secret");
+        assert_eq!(
+            spec.base_url.as_deref(),
+            Some("https://eu.pets.example.com/v1")
+        );
+
+        let tools = spec.tools(&HttpContext::default()).unwrap();
+        let definitions: Vec<&ToolDefinition> = tools.iter().map(|(d, _)| d).collect();
+        let names: Vec<&str> = definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["post_pets", "getPet"]);
+
+        assert_eq!(definitions[1].name, "getPet");
+        assert_eq!(definitions[1].description, "Get a pet by id");
+        assert_eq!(
+            definitions[1].parameters,
+            json!({
+                "type": "object",
+                "properties": {
+                    "petId": { "type": "integer", "description": "id of the pet" },
+                    "fields": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["petId"]
+            })
+        );
+
+        assert_eq!(definitions[0].name, "post_pets");
+        assert_eq!(
+            definitions[0].parameters["properties"]["body"]["properties"]["tags"]["items"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(definitions[0].parameters["required"], json!(["body"]));
+    }
+
+    #[test]
+    fn skip_unsupported_operations() {
+        let spec = OpenApiSpec::parse(
+            r#"{
+                "openapi": "3.0.0",
+                "servers": [{ "url": "https://api.example.com" }],
+                "paths": {
+                    "/session": {
+                        "get": {
+                            "operationId": "getSession",
+                            "parameters": [{ "name": "sid", "in": "cookie" }]
+                        }
+                    },
+                    "/status": { "get": { "operationId": "getStatus" } }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let tools = spec.tools(&HttpContext::default()).unwrap();
+        let names: Vec<&str> = tools.iter().map(|(d, _)| d.name.as_str()).collect();
+        assert_eq!(names, ["getStatus"]);
+    }
+
+    #[test]
+    fn reject_swagger_2() {
+        assert!(OpenApiSpec::parse(r#"{"swagger": "2.0", "paths": {}}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn call_operations() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/pets/7"))
+            .and(query_param("fields", "name"))
+            .and(query_param("api_key", "secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"id": 7})))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/pets"))
+            .and(header("X-Trace-Id", "t-1"))
+            .and(header("X-Api-Key", "secret"))
+            .and(query_param_is_missing("api_key"))
+            .and(body_json(json!({"name": "Rex"})))
+            .respond_with(ResponseTemplate::new(400).set_body_string("name taken"))
+            .mount(&server)
+            .await;
+
+        let tools = OpenApiSpec::parse(PETSTORE)
+            .unwrap()
+            .with_base_url(&format!("{}/v1", server.uri()))
+            .with_fixed_parameter("api_key", "secret")
+            .with_fixed_parameter("X-Api-Key", "secret")
+            .tools(&HttpContext::default())
+            .unwrap();
+
+        let pet = tools[1]
+            .1
+            .call(json!({"petId": 7, "fields": ["name"]}))
+            .await
+            .unwrap();
+        assert_eq!(pet, json!({"id": 7}));
+
+        let missing = tools[1].1.call(json!({})).await;
+        assert!(matches!(missing, Err(AgentError::Generic(m)) if m.contains("petId")));
+
+        let rejected = tools[0]
+            .1
+            .call(json!({"X-Trace-Id": "t-1", "body": {"name": "Rex"}}))
+            .await;
+        assert!(matches!(rejected, Err(AgentError::Generic(m)) if m.contains("name taken")));
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use crate::agent::{
+    policy::ToolPolicy,
+    tool::{ToolDefinition, ToolFunction},
+};
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    process::Command,
+};
+
+fn default_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_max_output_bytes() -> usize {
+    1024 * 1024
+}
+
+/// Manifest of a tool implemented as an external command, in JSON or YAML.
+/// The command reads the JSON arguments from stdin and writes a JSON result to stdout,
+/// a non-zero exit status fails the call.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolManifest {
+    pub name: String,
+    pub description: String,
+    /// Program to run, relative paths like `./tool.sh` are resolved against the manifest directory
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extra environment variables of the process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// JSON schema of the arguments
+    #[serde(default = "empty_parameters")]
+    pub parameters: Value,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Longest accepted stdout, the process is killed beyond it
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+}
+
+fn empty_parameters() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+impl ToolManifest {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            AgentError::Generic(format!(
+                "Failed to read tool manifest {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        let is_yaml = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yaml" | "yml")
+        );
+        let manifest = if is_yaml {
+            serde_yaml::from_str(&source).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&source).map_err(|e| e.to_string())
+        };
+        manifest.map_err(|e| {
+            AgentError::Generic(format!("Invalid tool manifest {}: {}", path.display(), e))
+        })
+    }
+
+    pub fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters: self.parameters.clone(),
+            example: None,
+        }
+    }
+}
+
+/// A tool running its manifest's command once per call
+pub struct SubprocessTool {
+    manifest: ToolManifest,
+    /// Directory of the manifest, the working directory of the process
+    dir: PathBuf,
+}
+
+impl SubprocessTool {
+    pub fn new(manifest: ToolManifest, dir: impl Into<PathBuf>) -> Self {
+        SubprocessTool {
+            manifest,
+            dir: dir.into(),
+        }
+    }
+
+    /// Calls are bounded by the manifest's `timeout_ms` alone, a policy timeout would cut them
+    pub fn policy(&self) -> ToolPolicy {
+        ToolPolicy {
+            timeout: None,
+            ..Default::default()
+        }
+    }
+
+    fn program(&self) -> PathBuf {
+        let command = Path::new(&self.manifest.command);
+        if command.is_relative() && command.components().count() > 1 {
+            self.dir.join(command)
+        } else {
+            command.to_path_buf()
+        }
+    }
+
+    async fn run(&self, args: Value) -> Result<Vec<u8>> {
+        let name = &self.manifest.name;
+        let mut child = Command::new(self.program())
+            .args(&self.manifest.args)
+            .envs(&self.manifest.env)
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| AgentError::Generic(format!("Failed to start tool {}: {}", name, e)))?;
+
+        // the input is written while the output is read, a process writing before it has
+        // read all of its input would block on a full pipe otherwise
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = args.to_string();
+        let write = async move {
+            // a process exiting without reading its input is not an error
+            let _ = stdin.write_all(input.as_bytes()).await;
+            Ok(())
+        };
+
+        let limit = self.manifest.max_output_bytes;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let read = async move {
+            let mut output = Vec::new();
+            stdout
+                .take(limit as u64 + 1)
+                .read_to_end(&mut output)
+                .await
+                .map_err(|e| {
+                    AgentError::Generic(format!("Failed to read output of tool {}: {}", name, e))
+                })?;
+            if output.len() > limit {
+                return Err(AgentError::Generic(format!(
+                    "Output of tool {} exceeds {} bytes",
+                    name, limit
+                )));
+            }
+            Ok(output)
+        };
+        let ((), output) = tokio::try_join!(write, read)?;
+
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| AgentError::Generic(format!("Failed to wait for tool {}: {}", name, e)))?;
+        if !status.success() {
+            return Err(AgentError::Generic(format!(
+                "Tool {} exited with {}",
+                name, status
+            )));
+        }
+
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl ToolFunction for SubprocessTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let timeout = Duration::from_millis(self.manifest.timeout_ms);
+        // the process is killed when the timed out future is dropped
+        let output = tokio::time::timeout(timeout, self.run(args))
+            .await
+            .map_err(|_| AgentError::Timeout(self.manifest.name.clone(), timeout))??;
+
+        let output = String::from_utf8_lossy(&output);
+        if output.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&output).map_err(|e| {
+            AgentError::Generic(format!(
+                "Tool {} wrote invalid JSON: {}",
+                self.manifest.name, e
+            ))
+        })
+    }
+}
+
+/// Load every `*.json`, `*.yaml` and `*.yml` manifest of a directory, sorted by file name
+pub fn load_tools_dir(dir: impl AsRef<Path>) -> Result<Vec<(ToolDefinition, SubprocessTool)>> {
+    let dir = dir.as_ref();
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        AgentError::Generic(format!(
+            "Failed to read tools directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("json" | "yaml" | "yml")
+            )
+        })
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let manifest = ToolManifest::from_file(&path)?;
+            Ok((manifest.definition(), SubprocessTool::new(manifest, dir)))
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn shell_tool(script: &str) -> SubprocessTool {
+        let manifest = ToolManifest {
+            name: "script".to_string(),
+            description: "Run a shell script".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: HashMap::new(),
+            parameters: empty_parameters(),
+            timeout_ms: 500,
+            max_output_bytes: 64,
+        };
+        SubprocessTool::new(manifest, std::env::temp_dir())
+    }
+
+    #[tokio::test]
+    async fn echo_arguments() {
+        let tool = shell_tool("cat");
+        let args = json!({"city": "Oslo"});
+        assert_eq!(tool.call(args.clone()).await.unwrap(), args);
+    }
+
+    #[tokio::test]
+    async fn enforce_limits() {
+        let slow = shell_tool("sleep 5");
+        assert!(matches!(
+            slow.call(json!({})).await,
+            Err(AgentError::Timeout(_, _))
+        ));
+
+        let verbose = shell_tool("head -c 1000 /dev/zero | tr '\\0' 'a'");
+        let result = verbose.call(json!({})).await;
+        assert!(matches!(result, Err(AgentError::Generic(m)) if m.contains("exceeds 64 bytes")));
+
+        let failing = shell_tool("echo '{}'; exit 3");
+        assert!(failing.call(json!({})).await.is_err());
+
+        let invalid = shell_tool("echo not json");
+        assert!(invalid.call(json!({})).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn answer_before_reading_input() {
+        // both pipes fill up unless the input is written while the output is read
+        let mut tool = shell_tool(
+            "printf '\"'; head -c 100000 /dev/zero | tr '\\0' 'a'; printf '\"'; cat > /dev/null",
+        );
+        tool.manifest.max_output_bytes = 200_000;
+
+        let output = tool.call(json!("b".repeat(1_000_000))).await.unwrap();
+        assert_eq!(output, json!("a".repeat(100_000)));
+    }
+
+    #[tokio::test]
+    async fn load_manifests_from_dir() {
+        let dir = std::env::temp_dir().join(format!("reactagent-tools-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("upper.yaml"),
+            "name: upper\ndescription: Uppercase the text\ncommand: ./upper.sh\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("upper.sh"),
+            "#!/bin/sh\ntr '[:lower:]' '[:upper:]'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(dir.join("upper.sh"), std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        std::fs::write(
+            dir.join("count.json"),
+            r#"{"name": "count", "description": "Count bytes", "command": "wc", "args": ["-c"]}"#,
+        )
+        .unwrap();
+
+        let tools = load_tools_dir(&dir).unwrap();
+        let names: Vec<&str> = tools.iter().map(|(d, _)| d.name.as_str()).collect();
+        assert_eq!(names, ["count", "upper"]);
+
+        assert_eq!(tools[0].1.call(json!("abc")).await.unwrap(), json!(5));
+        assert_eq!(tools[1].1.call(json!("abc")).await.unwrap(), json!("ABC"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,956 @@
+use crate::agent::tool::ToolFunction;
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Longest accepted expression, in characters
+const MAX_EXPRESSION_LEN: usize = 1000;
+/// Deepest accepted nesting of parentheses, function calls and signs
+const MAX_DEPTH: usize = 64;
+/// Approximate and repeating results are rounded to this many significant digits
+const SIGNIFICANT_DIGITS: usize = 15;
+/// Largest decimal exponent of a number literal, e.g. 1e1000
+const MAX_DECIMAL_EXPONENT: u32 = 1000;
+/// Fractions with more bits in numerator and denominator continue as floats, so huge powers
+/// cannot exhaust time and memory
+const MAX_EXACT_BITS: u64 = 64 * 1024;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CalculateArgs {
+    /// the expression, e.g. "(70 - 32) * 5 / 9", "sqrt(2) ^ 2" or "21.5 degC to degF"
+    pub expression: String,
+}
+
+/// Evaluates arithmetic expressions and unit conversions without executing any code
+pub struct CalculateTool;
+
+impl CalculateTool {
+    pub fn calculate(&self, args: CalculateArgs) -> Result<Value> {
+        let expression = args.expression.trim();
+        if expression.chars().count() > MAX_EXPRESSION_LEN {
+            return Err(AgentError::Generic(format!(
+                "Expression is longer than {} characters",
+                MAX_EXPRESSION_LEN
+            )));
+        }
+
+        let (value, unit) = match split_conversion(expression) {
+            Some((source, target)) => {
+                let (source, from) = split_unit(source)?;
+                let to = Unit::find(target)?;
+                (from.convert(evaluate(source)?, to)?, Some(to.names[0]))
+            }
+            None => (evaluate(expression)?, None),
+        };
+
+        let mut output = value.to_json();
+        if let Some(unit) = unit {
+            output["unit"] = json!(unit);
+        }
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl ToolFunction for CalculateTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: CalculateArgs = serde_json::from_value(args)
+            .map_err(|e| AgentError::Generic(format!("Invalid arguments: {}", e)))?;
+        self.calculate(args)
+    }
+}
+
+fn round_significant(value: f64) -> f64 {
+    format!("{:.*e}", SIGNIFICANT_DIGITS - 1, value)
+        .parse()
+        .unwrap_or(value)
+}
+
+/// A number, kept as an exact fraction until a function like sqrt or sin needs a float
+#[derive(Debug, Clone, PartialEq)]
+enum Number {
+    Exact(BigRational),
+    Approximate(f64),
+}
+
+impl Number {
+    /// Keep a fraction exact unless it grew too large to compute with
+    fn exact(value: BigRational) -> Number {
+        if value.numer().bits() + value.denom().bits() > MAX_EXACT_BITS {
+            Number::Approximate(value.to_f64().unwrap_or(f64::NAN))
+        } else {
+            Number::Exact(value)
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Number::Exact(value) => value.to_f64().unwrap_or(f64::NAN),
+            Number::Approximate(value) => *value,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Number::Exact(value) => value.is_zero(),
+            Number::Approximate(value) => *value == 0.0,
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        match self {
+            Number::Exact(_) => true,
+            Number::Approximate(value) => value.is_finite(),
+        }
+    }
+
+    /// Apply `exact` to two exact numbers and `approximate` as soon as one is a float
+    fn combine(
+        self,
+        other: Number,
+        exact: impl FnOnce(BigRational, BigRational) -> BigRational,
+        approximate: impl FnOnce(f64, f64) -> f64,
+    ) -> Number {
+        match (self, other) {
+            (Number::Exact(a), Number::Exact(b)) => Number::exact(exact(a, b)),
+            (a, b) => Number::Approximate(approximate(a.to_f64(), b.to_f64())),
+        }
+    }
+
+    /// Apply `exact` to an exact number, or `approximate` to a float
+    fn map(
+        self,
+        exact: impl FnOnce(BigRational) -> BigRational,
+        approximate: impl FnOnce(f64) -> f64,
+    ) -> Number {
+        match self {
+            Number::Exact(value) => Number::exact(exact(value)),
+            Number::Approximate(value) => Number::Approximate(approximate(value)),
+        }
+    }
+
+    /// Apply a function without exact results, e.g. sin
+    fn approximate(&self, function: impl FnOnce(f64) -> f64) -> Number {
+        Number::Approximate(function(self.to_f64()))
+    }
+
+    fn div(self, divisor: Number) -> Result<Number> {
+        if divisor.is_zero() {
+            return Err(AgentError::Generic("Division by zero".to_string()));
+        }
+        Ok(self.combine(divisor, |a, b| a / b, |a, b| a / b))
+    }
+
+    fn rem(self, divisor: Number) -> Result<Number> {
+        if divisor.is_zero() {
+            return Err(AgentError::Generic("Modulo by zero".to_string()));
+        }
+        Ok(self.combine(divisor, |a, b| a % b, |a, b| a % b))
+    }
+
+    /// Exact for integer exponents, as long as the result stays below MAX_EXACT_BITS
+    fn pow(self, exponent: Number) -> Result<Number> {
+        if let (Number::Exact(base), Number::Exact(power)) = (&self, &exponent)
+            && power.is_integer()
+            && let Some(power) = power.to_integer().to_i32()
+            && (base.numer().bits() + base.denom().bits()) * u64::from(power.unsigned_abs())
+                <= MAX_EXACT_BITS
+        {
+            if base.is_zero() && power < 0 {
+                return Err(AgentError::Generic("Division by zero".to_string()));
+            }
+            return Ok(Number::Exact(base.pow(power)));
+        }
+        Ok(Number::Approximate(self.to_f64().powf(exponent.to_f64())))
+    }
+
+    /// The exact square root of squares like 16 or 9/4, the approximate one otherwise
+    fn sqrt(self) -> Number {
+        if let Number::Exact(value) = &self
+            && !value.is_negative()
+        {
+            let (numer, denom) = (value.numer().sqrt(), value.denom().sqrt());
+            if &(&numer * &numer) == value.numer() && &(&denom * &denom) == value.denom() {
+                return Number::Exact(BigRational::new(numer, denom));
+            }
+        }
+        self.approximate(f64::sqrt)
+    }
+
+    fn round(self, digits: Option<&Number>) -> Number {
+        let Some(digits) = digits else {
+            return self.map(|x| x.round(), f64::round);
+        };
+        let places = digits.to_f64() as i32;
+        match (self, digits) {
+            (Number::Exact(value), Number::Exact(exact_places))
+                if exact_places.is_integer() && places.unsigned_abs() <= MAX_DECIMAL_EXPONENT =>
+            {
+                let scale = BigRational::from_integer(10.into()).pow(places);
+                Number::exact((value * &scale).round() / scale)
+            }
+            (value, _) => {
+                let scale = 10f64.powi(places);
+                value.approximate(|x| (x * scale).round() / scale)
+            }
+        }
+    }
+
+    /// The exact minimum or maximum if every argument is exact, as selected by `prefer`
+    fn select(arguments: &[Number], prefer: Ordering) -> Number {
+        let exact: Option<Vec<&BigRational>> = arguments
+            .iter()
+            .map(|argument| match argument {
+                Number::Exact(value) => Some(value),
+                Number::Approximate(_) => None,
+            })
+            .collect();
+        match exact {
+            Some(values) => Number::Exact(
+                values
+                    .into_iter()
+                    .reduce(|a, b| if b.cmp(a) == prefer { b } else { a })
+                    .cloned()
+                    .unwrap_or_default(),
+            ),
+            None => Number::Approximate(
+                arguments
+                    .iter()
+                    .map(Number::to_f64)
+                    .reduce(|a, b| match prefer {
+                        Ordering::Less => a.min(b),
+                        _ => a.max(b),
+                    })
+                    .unwrap_or(f64::NAN),
+            ),
+        }
+    }
+
+    /// The result fields: integers and decimals that JSON numbers hold exactly as numbers,
+    /// longer exact values as strings, repeating decimals rounded next to their fraction and
+    /// floats rounded with `"approximate": true`
+    fn to_json(&self) -> Value {
+        match self {
+            Number::Exact(value) if value.is_integer() => match value.to_integer().to_i64() {
+                Some(integer) => json!({ "result": integer }),
+                None => json!({ "result": value.to_integer().to_string() }),
+            },
+            Number::Exact(value) => match terminating_decimal(value) {
+                Some(decimal) => match decimal.parse::<f64>() {
+                    Ok(float) if float.to_string() == decimal => json!({ "result": float }),
+                    _ => json!({ "result": decimal }),
+                },
+                None => json!({
+                    "result": round_significant(self.to_f64()),
+                    "fraction": value.to_string(),
+                }),
+            },
+            Number::Approximate(value) => {
+                let value = round_significant(*value);
+                // integral results are written without a fraction
+                let result = if value.fract() == 0.0 && value.abs() < 2f64.powi(53) {
+                    json!(value as i64)
+                } else {
+                    json!(value)
+                };
+                json!({ "result": result, "approximate": true })
+            }
+        }
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, other: Number) -> Number {
+        self.combine(other, |a, b| a + b, |a, b| a + b)
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, other: Number) -> Number {
+        self.combine(other, |a, b| a - b, |a, b| a - b)
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, other: Number) -> Number {
+        self.combine(other, |a, b| a * b, |a, b| a * b)
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        self.map(|x| -x, |x| -x)
+    }
+}
+
+/// Parse a decimal like "1.5e-3" into an exact fraction
+fn parse_decimal(text: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    if digits.is_empty()
+        || !digits.chars().all(|c| c.is_ascii_digit())
+        || exponent.unsigned_abs() > MAX_DECIMAL_EXPONENT
+    {
+        return None;
+    }
+    let ten = BigRational::from_integer(10.into());
+    let scale = exponent.checked_sub(i32::try_from(fraction.len()).ok()?)?;
+    Some(BigRational::from_integer(digits.parse().ok()?) * ten.pow(scale))
+}
+
+/// The digits of a fraction whose decimal expansion ends, e.g. "0.375" for 3/8
+fn terminating_decimal(value: &BigRational) -> Option<String> {
+    let mut denom = value.denom().clone();
+    let (mut twos, mut fives) = (0, 0);
+    while (&denom % 2u32).is_zero() {
+        denom /= 2u32;
+        twos += 1;
+    }
+    while (&denom % 5u32).is_zero() {
+        denom /= 5u32;
+        fives += 1;
+    }
+    if !denom.is_one() {
+        return None;
+    }
+    let places = twos.max(fives);
+    let scaled = (value * BigRational::from_integer(BigInt::from(10).pow(places))).to_integer();
+    let digits = format!(
+        "{:0>width$}",
+        scaled.abs().to_string(),
+        width = places as usize + 1
+    );
+    let (whole, fraction) = digits.split_at(digits.len() - places as usize);
+    let sign = if scaled.is_negative() { "-" } else { "" };
+    Some(format!("{}{}.{}", sign, whole, fraction))
+}
+
+fn evaluate(expression: &str) -> Result<Number> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser {
+        expression,
+        tokens: &tokens,
+        position: 0,
+        depth: 0,
+    };
+    let value = parser.expression()?;
+    if let Some(token) = parser.peek() {
+        return Err(parser.unexpected(token));
+    }
+    if !value.is_finite() {
+        return Err(AgentError::Generic(format!(
+            "{} is not a finite number",
+            expression
+        )));
+    }
+    Ok(value)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    /// the exact value and the text of a number literal
+    Number(BigRational, String),
+    Ident(String),
+    Op(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// character offset in the expression
+    offset: usize,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let kind = if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // exponent, e.g. 1.5e-3
+            if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                let mut end = i + 1;
+                if end < chars.len() && matches!(chars[end], '+' | '-') {
+                    end += 1;
+                }
+                if end < chars.len() && chars[end].is_ascii_digit() {
+                    while end < chars.len() && chars[end].is_ascii_digit() {
+                        end += 1;
+                    }
+                    i = end;
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = parse_decimal(&text).ok_or_else(|| {
+                AgentError::Generic(format!("Invalid number {} at position {}", text, start))
+            })?;
+            TokenKind::Number(number, text)
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else if c == '*' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            TokenKind::Op('^')
+        } else if "+-*/%^(),".contains(c) {
+            i += 1;
+            TokenKind::Op(c)
+        } else {
+            return Err(AgentError::Generic(format!(
+                "Unexpected character '{}' at position {}",
+                c, start
+            )));
+        };
+        tokens.push(Token {
+            kind,
+            offset: start,
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser evaluating while it parses
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: &'a [Token],
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, op: char) -> bool {
+        let matched = matches!(self.peek(), Some(Token { kind: TokenKind::Op(c), .. }) if *c == op);
+        if matched {
+            self.position += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, op: char) -> Result<()> {
+        if self.eat(op) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(self.unexpected(token)),
+            None => Err(AgentError::Generic(format!(
+                "Expected '{}' at the end of {}",
+                op, self.expression
+            ))),
+        }
+    }
+
+    fn unexpected(&self, token: &Token) -> AgentError {
+        let text = match &token.kind {
+            TokenKind::Number(_, text) => text.clone(),
+            TokenKind::Ident(name) => name.clone(),
+            TokenKind::Op(c) => c.to_string(),
+        };
+        AgentError::Generic(format!(
+            "Unexpected '{}' at position {} in {}",
+            text, token.offset, self.expression
+        ))
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(AgentError::Generic(format!(
+                "Expression is nested deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // expression := term (("+" | "-") term)*
+    fn expression(&mut self) -> Result<Number> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value = value + self.term()?;
+            } else if self.eat('-') {
+                value = value - self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // term := unary (("*" | "/" | "%") unary)*
+    fn term(&mut self) -> Result<Number> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value = value * self.unary()?;
+            } else if self.eat('/') {
+                value = value.div(self.unary()?)?;
+            } else if self.eat('%') {
+                value = value.rem(self.unary()?)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // unary := ("-" | "+") unary | power
+    fn unary(&mut self) -> Result<Number> {
+        if self.eat('-') {
+            self.nested(|parser| Ok(-parser.unary()?))
+        } else if self.eat('+') {
+            self.nested(|parser| parser.unary())
+        } else {
+            self.power()
+        }
+    }
+
+    // power := primary ("^" unary)?, so -2^2 is -4 and 2^3^2 is 2^9
+    fn power(&mut self) -> Result<Number> {
+        let base = self.primary()?;
+        if self.eat('^') {
+            let exponent = self.nested(|parser| parser.unary())?;
+            return base.pow(exponent);
+        }
+        Ok(base)
+    }
+
+    // primary := number | constant | function "(" arguments ")" | "(" expression ")"
+    fn primary(&mut self) -> Result<Number> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(AgentError::Generic(format!(
+                "Unexpected end of {}",
+                self.expression
+            )));
+        };
+        self.position += 1;
+
+        match token.kind {
+            TokenKind::Number(value, _) => Ok(Number::Exact(value)),
+            TokenKind::Op('(') => {
+                let value = self.nested(|parser| parser.expression())?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            TokenKind::Ident(name) if self.eat('(') => {
+                let mut arguments = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        arguments.push(self.nested(|parser| parser.expression())?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                call_function(&name, &arguments)
+            }
+            TokenKind::Ident(name) => match name.as_str() {
+                "pi" => Ok(Number::Approximate(std::f64::consts::PI)),
+                "e" => Ok(Number::Approximate(std::f64::consts::E)),
+                _ => Err(AgentError::Generic(format!(
+                    "Unknown name {} at position {}, functions need parentheses and units \
+                     are only supported in \"<value> <unit> to <unit>\"",
+                    name, token.offset
+                ))),
+            },
+            TokenKind::Op(_) => Err(self.unexpected(&token)),
+        }
+    }
+}
+
+fn call_function(name: &str, arguments: &[Number]) -> Result<Number> {
+    let arity_error = |expected: &str| {
+        AgentError::Generic(format!(
+            "{} takes {} arguments, got {}",
+            name,
+            expected,
+            arguments.len()
+        ))
+    };
+    let one = || match arguments {
+        [x] => Ok(x.clone()),
+        _ => Err(arity_error("1")),
+    };
+
+    match name {
+        "sqrt" => Ok(one()?.sqrt()),
+        "cbrt" => Ok(one()?.approximate(f64::cbrt)),
+        "abs" => Ok(one()?.map(|x| x.abs(), f64::abs)),
+        "exp" => Ok(one()?.approximate(f64::exp)),
+        "ln" => Ok(one()?.approximate(f64::ln)),
+        "log2" => Ok(one()?.approximate(f64::log2)),
+        "log10" => Ok(one()?.approximate(f64::log10)),
+        "log" => match arguments {
+            [x] => Ok(x.approximate(f64::log10)),
+            [x, base] => Ok(x.approximate(|x| x.log(base.to_f64()))),
+            _ => Err(arity_error("1 or 2")),
+        },
+        "sin" => Ok(one()?.approximate(f64::sin)),
+        "cos" => Ok(one()?.approximate(f64::cos)),
+        "tan" => Ok(one()?.approximate(f64::tan)),
+        "asin" => Ok(one()?.approximate(f64::asin)),
+        "acos" => Ok(one()?.approximate(f64::acos)),
+        "atan" => Ok(one()?.approximate(f64::atan)),
+        "floor" => Ok(one()?.map(|x| x.floor(), f64::floor)),
+        "ceil" => Ok(one()?.map(|x| x.ceil(), f64::ceil)),
+        "round" => match arguments {
+            [x] => Ok(x.clone().round(None)),
+            [x, digits] => Ok(x.clone().round(Some(digits))),
+            _ => Err(arity_error("1 or 2")),
+        },
+        "pow" => match arguments {
+            [x, y] => x.clone().pow(y.clone()),
+            _ => Err(arity_error("2")),
+        },
+        "min" | "max" if arguments.is_empty() => Err(arity_error("at least 1")),
+        "min" => Ok(Number::select(arguments, Ordering::Less)),
+        "max" => Ok(Number::select(arguments, Ordering::Greater)),
+        _ => Err(AgentError::Generic(format!("Unknown function {}", name))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Length,
+    Mass,
+    Temperature,
+    Time,
+    Speed,
+    Pressure,
+    Volume,
+}
+
+/// A unit as a linear map to the base unit of its dimension: `base = value * factor + offset`,
+/// with factor and offset as exact fractions `(numerator, denominator)`
+#[derive(Debug, PartialEq)]
+struct Unit {
+    names: &'static [&'static str],
+    dimension: Dimension,
+    factor: (i64, i64),
+    offset: (i64, i64),
+}
+
+const fn unit(names: &'static [&'static str], dimension: Dimension, factor: (i64, i64)) -> Unit {
+    Unit {
+        names,
+        dimension,
+        factor,
+        offset: (0, 1),
+    }
+}
+
+fn fraction((numer, denom): (i64, i64)) -> Number {
+    Number::Exact(BigRational::new(numer.into(), denom.into()))
+}
+
+const UNITS: &[Unit] = &[
+    unit(
+        &["m", "meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        (1, 1),
+    ),
+    unit(
+        &["km", "kilometer", "kilometers"],
+        Dimension::Length,
+        (1000, 1),
+    ),
+    unit(
+        &["cm", "centimeter", "centimeters"],
+        Dimension::Length,
+        (1, 100),
+    ),
+    unit(
+        &["mm", "millimeter", "millimeters"],
+        Dimension::Length,
+        (1, 1000),
+    ),
+    unit(
+        &["mi", "mile", "miles"],
+        Dimension::Length,
+        (1_609_344, 1000),
+    ),
+    unit(&["yd", "yard", "yards"], Dimension::Length, (9144, 10_000)),
+    unit(&["ft", "foot", "feet"], Dimension::Length, (3048, 10_000)),
+    unit(&["in", "inch", "inches"], Dimension::Length, (254, 10_000)),
+    unit(&["nmi"], Dimension::Length, (1852, 1)),
+    unit(&["kg", "kilogram", "kilograms"], Dimension::Mass, (1, 1)),
+    unit(&["g", "gram", "grams"], Dimension::Mass, (1, 1000)),
+    unit(
+        &["mg", "milligram", "milligrams"],
+        Dimension::Mass,
+        (1, 1_000_000),
+    ),
+    unit(&["t", "tonne", "tonnes"], Dimension::Mass, (1000, 1)),
+    unit(
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        (45_359_237, 100_000_000),
+    ),
+    unit(
+        &["oz", "ounce", "ounces"],
+        Dimension::Mass,
+        (28_349_523_125, 1_000_000_000_000),
+    ),
+    unit(&["K", "kelvin"], Dimension::Temperature, (1, 1)),
+    Unit {
+        names: &["degC", "°C", "C", "celsius"],
+        dimension: Dimension::Temperature,
+        factor: (1, 1),
+        offset: (27_315, 100),
+    },
+    Unit {
+        names: &["degF", "°F", "F", "fahrenheit"],
+        dimension: Dimension::Temperature,
+        factor: (5, 9),
+        offset: (45_967, 180),
+    },
+    unit(&["s", "sec", "second", "seconds"], Dimension::Time, (1, 1)),
+    unit(
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        (1, 1000),
+    ),
+    unit(&["min", "minute", "minutes"], Dimension::Time, (60, 1)),
+    unit(&["h", "hr", "hour", "hours"], Dimension::Time, (3600, 1)),
+    unit(&["day", "days"], Dimension::Time, (86_400, 1)),
+    unit(&["week", "weeks"], Dimension::Time, (604_800, 1)),
+    unit(&["m/s"], Dimension::Speed, (1, 1)),
+    unit(&["km/h", "kph", "kmh"], Dimension::Speed, (5, 18)),
+    unit(&["mph", "mi/h"], Dimension::Speed, (44_704, 100_000)),
+    unit(&["ft/s"], Dimension::Speed, (3048, 10_000)),
+    unit(&["kn", "knot", "knots"], Dimension::Speed, (1852, 3600)),
+    unit(&["Pa", "pascal"], Dimension::Pressure, (1, 1)),
+    unit(&["hPa"], Dimension::Pressure, (100, 1)),
+    unit(&["kPa"], Dimension::Pressure, (1000, 1)),
+    unit(&["bar"], Dimension::Pressure, (100_000, 1)),
+    unit(&["atm"], Dimension::Pressure, (101_325, 1)),
+    unit(
+        &["psi"],
+        Dimension::Pressure,
+        (6_894_757_293_168, 1_000_000_000),
+    ),
+    unit(
+        &["mmHg"],
+        Dimension::Pressure,
+        (133_322_387_415, 1_000_000_000),
+    ),
+    unit(&["inHg"], Dimension::Pressure, (33_863_886_666, 10_000_000)),
+    unit(
+        &["l", "L", "liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        (1, 1000),
+    ),
+    unit(
+        &["ml", "mL", "milliliter", "milliliters"],
+        Dimension::Volume,
+        (1, 1_000_000),
+    ),
+    unit(&["m3"], Dimension::Volume, (1, 1)),
+    unit(
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        (3_785_411_784, 1_000_000_000_000),
+    ),
+];
+
+impl Unit {
+    /// Look a unit up by name, falling back to a case-insensitive match
+    fn find(name: &str) -> Result<&'static Unit> {
+        let name = name.trim();
+        UNITS
+            .iter()
+            .find(|unit| unit.names.contains(&name))
+            .or_else(|| {
+                UNITS.iter().find(|unit| {
+                    unit.names
+                        .iter()
+                        .any(|candidate| candidate.eq_ignore_ascii_case(name))
+                })
+            })
+            .ok_or_else(|| AgentError::Generic(format!("Unknown unit {}", name)))
+    }
+
+    fn convert(&self, value: Number, to: &Unit) -> Result<Number> {
+        if self.dimension != to.dimension {
+            return Err(AgentError::Generic(format!(
+                "Cannot convert {} ({:?}) to {} ({:?})",
+                self.names[0], self.dimension, to.names[0], to.dimension
+            )));
+        }
+        (value * fraction(self.factor) + fraction(self.offset) - fraction(to.offset))
+            .div(fraction(to.factor))
+    }
+}
+
+/// Split "<expression> <unit> to <unit>" at the last " to "
+fn split_conversion(expression: &str) -> Option<(&str, &str)> {
+    let index = expression.to_ascii_lowercase().rfind(" to ")?;
+    Some((&expression[..index], &expression[index + 4..]))
+}
+
+/// Split the unit off the end of the value of a conversion, e.g. "(20 + 1.5) degC"
+fn split_unit(source: &str) -> Result<(&str, &'static Unit)> {
+    let source = source.trim_end();
+    let start = source
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || matches!(c, '/' | '°'))
+        .last()
+        .map(|(index, _)| index)
+        .unwrap_or(source.len());
+    // a unit starts with a letter, so the digits of "21.5C" stay with the value
+    let start = source[start..]
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_digit() && *c != '.')
+        .map_or(source.len(), |(index, _)| start + index);
+
+    if start == source.len() {
+        return Err(AgentError::Generic(format!(
+            "Missing unit of the value to convert in {}",
+            source
+        )));
+    }
+    Ok((&source[..start], Unit::find(&source[start..])?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculate(expression: &str) -> Result<Value> {
+        CalculateTool.calculate(CalculateArgs {
+            expression: expression.to_string(),
+        })
+    }
+
+    #[test]
+    fn evaluate_arithmetic() {
+        assert_eq!(calculate("1 + 2 * 3").unwrap(), json!({"result": 7}));
+        assert_eq!(calculate("(1 + 2) * 3").unwrap(), json!({"result": 9}));
+        assert_eq!(calculate("0.1 + 0.2").unwrap(), json!({"result": 0.3}));
+        assert_eq!(calculate("-2 ^ 2").unwrap(), json!({"result": -4}));
+        assert_eq!(calculate("2 ** 3 ^ 2").unwrap(), json!({"result": 512}));
+        assert_eq!(calculate("7 % 4 - 1.5e1").unwrap(), json!({"result": -12}));
+        assert_eq!(
+            calculate("(70 - 32) * 5 / 9").unwrap()["result"],
+            json!(21.1111111111111)
+        );
+        assert_eq!(
+            calculate("sqrt(2) ^ 2").unwrap(),
+            json!({"result": 2, "approximate": true})
+        );
+        assert_eq!(calculate("sqrt(9 / 4)").unwrap(), json!({"result": 1.5}));
+        assert_eq!(
+            calculate("max(1, round(pi * 2, 1), log(100))").unwrap(),
+            json!({"result": 6.3, "approximate": true})
+        );
+        assert_eq!(
+            calculate("round(2 / 3, 2) + floor(-1.5)").unwrap(),
+            json!({"result": -1.33})
+        );
+
+        // exact beyond the 2^53 of double precision
+        assert_eq!(
+            calculate("(2^53 + 1) - 2^53").unwrap(),
+            json!({"result": 1})
+        );
+        assert_eq!(
+            calculate("9007199254740993 * 3").unwrap(),
+            json!({"result": 27021597764222979i64})
+        );
+        assert_eq!(
+            calculate("2^64").unwrap(),
+            json!({"result": "18446744073709551616"})
+        );
+        assert_eq!(
+            calculate("1 / 3").unwrap(),
+            json!({"result": 0.333333333333333, "fraction": "1/3"})
+        );
+        assert_eq!(
+            calculate("1e-20 + 1").unwrap(),
+            json!({"result": "1.00000000000000000001"})
+        );
+    }
+
+    #[test]
+    fn convert_units() {
+        assert_eq!(
+            calculate("21.5 degC to degF").unwrap(),
+            json!({"result": 70.7, "unit": "degF"})
+        );
+        assert_eq!(
+            calculate("(68 + 2)°F to C").unwrap(),
+            json!({"result": 21.1111111111111, "fraction": "190/9", "unit": "degC"})
+        );
+        assert_eq!(
+            calculate("36km/h to m/s").unwrap(),
+            json!({"result": 10, "unit": "m/s"})
+        );
+        assert_eq!(
+            calculate("1013.25 hPa to atm").unwrap(),
+            json!({"result": 1, "unit": "atm"})
+        );
+        assert_eq!(
+            calculate("3 MILES to km").unwrap(),
+            json!({"result": 4.828032, "unit": "km"})
+        );
+    }
+
+    #[test]
+    fn report_errors() {
+        let error = |expression: &str| match calculate(expression) {
+            Err(AgentError::Generic(message)) => message,
+            other => panic!("{} should fail, got {:?}", expression, other.ok()),
+        };
+
+        assert!(error("1 + * 2").contains("Unexpected '*' at position 4"));
+        assert!(error("(1 + 2").contains("Expected ')'"));
+        assert!(error("1 / (2 - 2)").contains("Division by zero"));
+        assert!(error("sqrt(-1)").contains("not a finite number"));
+        assert!(error("2 ^ 2 ^ 20").contains("not a finite number"));
+        assert!(error("0 ^ -1").contains("Division by zero"));
+        assert!(error("foo(1)").contains("Unknown function foo"));
+        assert!(error("pow(2)").contains("pow takes 2 arguments"));
+        assert!(error("x + 1").contains("Unknown name x"));
+        assert!(error("1; rm -rf /").contains("Unexpected character ';'"));
+        assert!(error("5 km to kg").contains("Cannot convert km"));
+        assert!(error("5 to km").contains("Missing unit"));
+        assert!(error(&"(".repeat(100)).contains("nested deeper"));
+    }
+}
@@ -0,0 +1,493 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use crate::agent::tool::ToolFunction;
+use crate::error::AgentError;
+use crate::http::{HttpConfig, HttpContext};
+use crate::prelude::*;
+use async_trait::async_trait;
+use reqwest::{
+    Response, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Restrictions of the `fetch_url` tool
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    /// Domains that may be fetched, including their subdomains; any domain if empty
+    pub allowed_domains: Vec<String>,
+    /// Domains that are never fetched, including their subdomains, checked before the allowlist
+    pub denied_domains: Vec<String>,
+    /// Bytes of the response body read, the rest is dropped
+    pub max_bytes: usize,
+    /// Characters of the returned content
+    pub max_chars: usize,
+    /// Redirects followed, every target is checked like the requested url
+    pub max_redirects: usize,
+    /// Fetch from loopback, link-local and private addresses too, e.g. a local server
+    pub allow_private_addresses: bool,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            max_bytes: 2 * 1024 * 1024,
+            max_chars: 8000,
+            max_redirects: 5,
+            allow_private_addresses: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct FetchUrlArgs {
+    /// http or https url of the page, e.g. "https://example.com/docs"
+    pub url: String,
+}
+
+/// How a response body is turned into the observation
+#[derive(Debug, PartialEq)]
+enum ContentKind {
+    Html,
+    Text,
+}
+
+impl ContentKind {
+    fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "text/html" | "application/xhtml+xml" => Some(ContentKind::Html),
+            // servers omitting the type mostly send text
+            "" | "application/json" | "application/xml" | "application/javascript" => {
+                Some(ContentKind::Text)
+            }
+            m if m.starts_with("text/") || m.ends_with("+json") || m.ends_with("+xml") => {
+                Some(ContentKind::Text)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// GET a web page and read it as text, HTML is converted to markdown
+pub struct FetchUrlTool {
+    config: FetchConfig,
+    http: HttpContext,
+}
+
+impl FetchUrlTool {
+    /// Redirects are followed by the tool itself to check every target, and names are
+    /// resolved to public addresses only, so it builds its own client from `http`
+    /// instead of sharing one
+    pub fn new(config: FetchConfig, http: HttpConfig) -> Result<Self> {
+        let http = HttpConfig {
+            follow_redirects: false,
+            ..http
+        };
+        let http = if config.allow_private_addresses {
+            HttpContext::new(http)?
+        } else {
+            HttpContext::with_resolver(http, Arc::new(PublicResolver))?
+        };
+        Ok(FetchUrlTool { config, http })
+    }
+
+    async fn check_url(&self, url: &Url) -> Result<()> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AgentError::Generic(format!(
+                "Unsupported url scheme {}, only http and https are fetched",
+                url.scheme()
+            )));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| AgentError::Generic(format!("Url {} has no host", url)))?
+            .to_lowercase();
+        let matches = |domain: &String| {
+            let domain = domain.trim_start_matches('.').to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        };
+
+        if self.config.denied_domains.iter().any(matches)
+            || (!self.config.allowed_domains.is_empty()
+                && !self.config.allowed_domains.iter().any(matches))
+        {
+            return Err(AgentError::Generic(format!(
+                "Fetching from {} is not allowed",
+                host
+            )));
+        }
+        if self.config.allow_private_addresses {
+            return Ok(());
+        }
+
+        // the client resolves names with the same check when it connects, this gives a
+        // clear error early
+        let literal = host.trim_start_matches('[').trim_end_matches(']');
+        match literal.parse::<IpAddr>() {
+            Ok(address) if !is_public(&address) => Err(AgentError::Generic(format!(
+                "Fetching from {} is not allowed, it is the non-public address {}",
+                host, address
+            ))),
+            Ok(_) => Ok(()),
+            Err(_) => public_addresses(&host)
+                .await
+                .map(|_| ())
+                .map_err(AgentError::Generic),
+        }
+    }
+
+    pub async fn fetch_url(&self, args: FetchUrlArgs) -> Result<Value> {
+        let mut url = Url::parse(&args.url)
+            .map_err(|e| AgentError::Generic(format!("Invalid url {}: {}", args.url, e)))?;
+
+        for _ in 0..=self.config.max_redirects {
+            self.check_url(&url).await?;
+            let response = self.http.client().get(url.clone()).send().await?;
+            if !response.status().is_redirection() {
+                return self.read_response(url, response).await;
+            }
+
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| {
+                    AgentError::Generic(format!("Redirect from {} has no location", url))
+                })?;
+            url = url.join(location).map_err(|e| {
+                AgentError::Generic(format!("Invalid redirect location {}: {}", location, e))
+            })?;
+        }
+
+        Err(AgentError::Generic(format!(
+            "Too many redirects fetching {}",
+            args.url
+        )))
+    }
+
+    async fn read_response(&self, url: Url, mut response: Response) -> Result<Value> {
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AgentError::Generic(format!(
+                "Fetching {} failed with status {}",
+                url, status
+            )));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        let kind = ContentKind::from_mime(&mime).ok_or_else(|| {
+            AgentError::Generic(format!(
+                "Content type {} of {} cannot be read as text",
+                mime, url
+            ))
+        })?;
+
+        let limit = self.config.max_bytes;
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await? {
+            let room = limit - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let text = match kind {
+            ContentKind::Html => html2text::config::plain()
+                .string_from_read(body.as_slice(), 120)
+                .map_err(|e| {
+                    AgentError::Generic(format!("Failed to convert HTML of {}: {}", url, e))
+                })?,
+            ContentKind::Text => String::from_utf8_lossy(&body).into_owned(),
+        };
+        let (content, trimmed) = trim_text(&text, self.config.max_chars);
+
+        Ok(json!({
+            "url": url.as_str(),
+            "content_type": mime,
+            "content": content,
+            "truncated": truncated || trimmed,
+        }))
+    }
+}
+
+/// Resolve a host name, failing if any of its addresses is not public
+async fn public_addresses(host: &str) -> std::result::Result<Vec<SocketAddr>, String> {
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    match addresses.iter().find(|address| !is_public(&address.ip())) {
+        Some(address) => Err(format!(
+            "Fetching from {} is not allowed, it resolves to the non-public address {}",
+            host,
+            address.ip()
+        )),
+        None => Ok(addresses),
+    }
+}
+
+/// Resolver of the fetch client, so the addresses connected to are the vetted ones even if
+/// a name resolves differently by the time of the request
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses = public_addresses(&host).await?;
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether an address is reachable on the internet, rather than the host or its networks
+fn is_public(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // shared address space of carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(&IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Drop trailing spaces and runs of blank lines, and keep the first `max_chars` characters
+fn trim_text(text: &str, max_chars: usize) -> (String, bool) {
+    let mut trimmed = String::new();
+    let mut blank_lines = 0;
+    for line in text.trim().lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        trimmed.push_str(line);
+        trimmed.push('\n');
+    }
+
+    let trimmed = trimmed.trim_end();
+    match trimmed.char_indices().nth(max_chars) {
+        Some((end, _)) => (trimmed[..end].to_string(), true),
+        None => (trimmed.to_string(), false),
+    }
+}
+
+#[async_trait]
+impl ToolFunction for FetchUrlTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: FetchUrlArgs = serde_json::from_value(args)
+            .map_err(|e| AgentError::Generic(format!("Invalid arguments: {}", e)))?;
+        self.fetch_url(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    const PAGE: &str = "<html><head><title>Docs</title><script>track()</script></head>\
+        <body><h1>Weather API</h1><p>Call <code>get_weather</code> with a city.</p>\
+        <ul><li>Celsius</li><li>Fahrenheit</li></ul></body></html>";
+
+    fn fetcher(configure: impl FnOnce(&mut FetchConfig)) -> FetchUrlTool {
+        // the mock server listens on the loopback address
+        let mut config = FetchConfig {
+            allow_private_addresses: true,
+            ..Default::default()
+        };
+        configure(&mut config);
+        FetchUrlTool::new(config, HttpConfig::default()).unwrap()
+    }
+
+    async fn server() -> MockServer {
+        let server = MockServer::start().await;
+        let routes = [
+            ("/page", "text/html; charset=utf-8", PAGE.to_string()),
+            ("/data", "application/json", r#"{"temp": 21}"#.to_string()),
+            ("/logo.png", "image/png", "\u{89}PNG".to_string()),
+            ("/long", "text/plain", "abcdefghij\n".repeat(100)),
+        ];
+        for (route, content_type, body) in routes {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body, content_type))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/moved"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/page"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/away"))
+            .respond_with(
+                ResponseTemplate::new(302).insert_header("Location", "http://denied.test/"),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn trim_blank_lines() {
+        assert_eq!(
+            trim_text("a  \n\n\n\nb\n", 100),
+            ("a\n\nb".to_string(), false)
+        );
+        assert_eq!(trim_text("héllo", 2), ("hé".to_string(), true));
+    }
+
+    #[tokio::test]
+    async fn fetch_content_types() {
+        let server = server().await;
+        let tool = fetcher(|_| {});
+
+        let page = tool
+            .call(json!({"url": format!("{}/moved", server.uri())}))
+            .await
+            .unwrap();
+        assert_eq!(page["url"], format!("{}/page", server.uri()));
+        assert_eq!(page["content_type"], "text/html");
+        let content = page["content"].as_str().unwrap();
+        assert!(content.starts_with("# Weather API"), "{}", content);
+        assert!(content.contains("* Celsius"), "{}", content);
+        assert!(!content.contains("<p>") && !content.contains("track()"));
+
+        let data = tool
+            .call(json!({"url": format!("{}/data", server.uri())}))
+            .await
+            .unwrap();
+        assert_eq!(data["content"], r#"{"temp": 21}"#);
+
+        let image = tool
+            .call(json!({"url": format!("{}/logo.png", server.uri())}))
+            .await;
+        assert!(image.is_err());
+    }
+
+    #[tokio::test]
+    async fn enforce_limits() {
+        let server = server().await;
+
+        let small = fetcher(|config| config.max_bytes = 25);
+        let long = small
+            .call(json!({"url": format!("{}/long", server.uri())}))
+            .await
+            .unwrap();
+        assert_eq!(long["content"], "abcdefghij\nabcdefghij\nabc");
+        assert_eq!(long["truncated"], true);
+
+        let allowlisted = fetcher(|config| config.allowed_domains = vec!["example.com".into()]);
+        assert!(
+            allowlisted
+                .call(json!({"url": format!("{}/page", server.uri())}))
+                .await
+                .is_err()
+        );
+
+        let denylisted = fetcher(|config| config.denied_domains = vec!["denied.test".into()]);
+        let redirected = denylisted
+            .call(json!({"url": format!("{}/away", server.uri())}))
+            .await;
+        assert!(
+            matches!(redirected, Err(AgentError::Generic(m)) if m.contains("denied.test is not allowed"))
+        );
+
+        assert!(
+            denylisted
+                .call(json!({"url": "file:///etc/passwd"}))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_private_addresses() {
+        let server = server().await;
+        let public_only = fetcher(|config| config.allow_private_addresses = false);
+
+        for url in [
+            format!("{}/page", server.uri()),
+            "http://localhost/".to_string(),
+            "http://169.254.169.254/latest/meta-data".to_string(),
+            "http://10.0.0.1/".to_string(),
+            "http://100.64.0.1/".to_string(),
+            "http://[::1]/".to_string(),
+            "http://[fd00::1]/".to_string(),
+            "http://[::ffff:192.168.0.1]/".to_string(),
+        ] {
+            let fetched = public_only.call(json!({ "url": url })).await;
+            assert!(
+                matches!(fetched, Err(AgentError::Generic(ref m)) if m.contains("non-public")),
+                "{}: {:?}",
+                url,
+                fetched
+            );
+        }
+
+        // names are resolved by the client with the same check, not only by check_url
+        let pinned = public_only
+            .http
+            .client()
+            .get(format!("http://localhost:{}/page", server.address().port()))
+            .send()
+            .await;
+        assert!(format!("{:?}", pinned.unwrap_err()).contains("non-public"));
+
+        assert!(is_public(&"93.184.215.14".parse().unwrap()));
+        assert!(is_public(&"2606:2800:21f:cb07::1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,630 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::agent::{
+    approval::RiskLevel,
+    policy::{PolicyTool, ToolPolicy},
+    tool::{ToolDefinition, ToolFunction},
+};
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use regex::RegexBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+/// Settings of the file system tools
+#[derive(Debug, Clone)]
+pub struct FsConfig {
+    /// Directory the tools are confined to
+    pub root: PathBuf,
+    /// Bytes returned by a single read
+    pub max_read_bytes: usize,
+    /// Bytes accepted by a single write
+    pub max_write_bytes: usize,
+    /// Entries of a listing and matches of a search
+    pub max_results: usize,
+    /// Report what writes would do without touching the files
+    pub dry_run: bool,
+}
+
+impl FsConfig {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsConfig {
+            root: root.into(),
+            max_read_bytes: 64 * 1024,
+            max_write_bytes: 1024 * 1024,
+            max_results: 200,
+            dry_run: false,
+        }
+    }
+}
+
+/// Resolves paths given by the model to paths inside the root
+#[derive(Debug, Clone)]
+pub struct PathJail {
+    root: PathBuf,
+}
+
+impl PathJail {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|e| {
+            AgentError::Generic(format!("Invalid root directory {}: {}", root.display(), e))
+        })?;
+        Ok(PathJail { root })
+    }
+
+    /// Resolve a relative path, which may not exist yet. `..` is rejected and symlinks
+    /// are followed, so a link pointing out of the root is rejected as well, and so is a
+    /// link to a missing target as it cannot be checked.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path);
+        for component in relative.components() {
+            match component {
+                Component::Normal(_) | Component::CurDir => {}
+                _ => {
+                    return Err(AgentError::Generic(format!(
+                        "Path {} must be relative to the root and must not contain \"..\"",
+                        path
+                    )));
+                }
+            }
+        }
+
+        // canonicalize the deepest existing ancestor, the missing rest cannot be a link.
+        // `exists` follows links, a dangling one would be taken for a missing name.
+        let mut existing = self.root.join(relative);
+        let mut missing = Vec::new();
+        while existing.symlink_metadata().is_err() {
+            match (existing.file_name(), existing.parent()) {
+                (Some(name), Some(parent)) => {
+                    missing.push(name.to_os_string());
+                    existing = parent.to_path_buf();
+                }
+                _ => break,
+            }
+        }
+        if !existing.exists() {
+            return Err(AgentError::Generic(format!(
+                "Path {} goes through a link to a missing target",
+                path
+            )));
+        }
+
+        let mut resolved = existing
+            .canonicalize()
+            .map_err(|e| AgentError::Generic(format!("Invalid path {}: {}", path, e)))?;
+        if !resolved.starts_with(&self.root) {
+            return Err(AgentError::Generic(format!(
+                "Path {} is outside of the root directory",
+                path
+            )));
+        }
+        resolved.extend(missing.into_iter().rev());
+        Ok(resolved)
+    }
+
+    /// Path relative to the root, as shown to the model
+    fn display(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        match relative.to_string_lossy() {
+            s if s.is_empty() => ".".to_string(),
+            s => s.into_owned(),
+        }
+    }
+}
+
+fn parse_args<T: DeserializeOwned>(args: Value) -> Result<T> {
+    serde_json::from_value(args)
+        .map_err(|e| AgentError::Generic(format!("Invalid arguments: {}", e)))
+}
+
+fn io_error(action: &str, path: &str, e: std::io::Error) -> AgentError {
+    AgentError::Generic(format!("Failed to {} {}: {}", action, path, e))
+}
+
+/// Run blocking file system work off the async runtime
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AgentError::Generic(format!("File system task failed: {}", e)))?
+}
+
+struct FsContext {
+    config: FsConfig,
+    jail: PathJail,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ReadFileArgs {
+    /// path of the file, relative to the root directory
+    pub path: String,
+    /// byte offset to start reading at, 0 by default
+    pub offset: Option<u64>,
+}
+
+pub struct ReadFileTool {
+    context: Arc<FsContext>,
+}
+
+#[async_trait]
+impl ToolFunction for ReadFileTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: ReadFileArgs = parse_args(args)?;
+        let context = self.context.clone();
+
+        blocking(move || {
+            let path = context.jail.resolve(&args.path)?;
+            let mut file = File::open(&path).map_err(|e| io_error("read", &args.path, e))?;
+            let size = file
+                .metadata()
+                .map_err(|e| io_error("read", &args.path, e))?
+                .len();
+            let offset = args.offset.unwrap_or(0);
+            file.seek(SeekFrom::Start(offset))
+                .map_err(|e| io_error("read", &args.path, e))?;
+
+            let mut content = Vec::new();
+            file.take(context.config.max_read_bytes as u64)
+                .read_to_end(&mut content)
+                .map_err(|e| io_error("read", &args.path, e))?;
+
+            let end = offset + content.len() as u64;
+            Ok(json!({
+                "path": context.jail.display(&path),
+                "size": size,
+                "content": String::from_utf8_lossy(&content),
+                // read again from this offset to get the rest
+                "next_offset": if end < size { Some(end) } else { None },
+            }))
+        })
+        .await
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ListDirArgs {
+    /// path of the directory, relative to the root directory, "." by default
+    pub path: Option<String>,
+}
+
+pub struct ListDirTool {
+    context: Arc<FsContext>,
+}
+
+#[async_trait]
+impl ToolFunction for ListDirTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: ListDirArgs = parse_args(args)?;
+        let context = self.context.clone();
+
+        blocking(move || {
+            let requested = args.path.unwrap_or_else(|| ".".to_string());
+            let path = context.jail.resolve(&requested)?;
+            let mut entries: Vec<Value> = std::fs::read_dir(&path)
+                .map_err(|e| io_error("list", &requested, e))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let metadata = entry.path().symlink_metadata().ok();
+                    let kind = match &metadata {
+                        Some(m) if m.is_symlink() => "symlink",
+                        Some(m) if m.is_dir() => "dir",
+                        _ => "file",
+                    };
+                    json!({
+                        "name": entry.file_name().to_string_lossy(),
+                        "type": kind,
+                        "size": metadata.filter(|m| m.is_file()).map(|m| m.len()),
+                    })
+                })
+                .collect();
+            entries.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+            let truncated = entries.len() > context.config.max_results;
+            entries.truncate(context.config.max_results);
+            Ok(json!({
+                "path": context.jail.display(&path),
+                "entries": entries,
+                "truncated": truncated,
+            }))
+        })
+        .await
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WriteFileArgs {
+    /// path of the file, relative to the root directory; missing directories are created
+    pub path: String,
+    /// the new content of the file
+    pub content: String,
+    /// append to the file instead of replacing it
+    pub append: Option<bool>,
+}
+
+/// Open a resolved path without following a link put in its place since it was resolved
+fn open_for_write(path: &Path, append: bool, existed: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if !existed {
+        options.create_new(true);
+    } else if append {
+        options.append(true);
+    } else {
+        options.truncate(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    options.open(path)
+}
+
+pub struct WriteFileTool {
+    context: Arc<FsContext>,
+}
+
+#[async_trait]
+impl ToolFunction for WriteFileTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: WriteFileArgs = parse_args(args)?;
+        let context = self.context.clone();
+
+        blocking(move || {
+            if args.content.len() > context.config.max_write_bytes {
+                return Err(AgentError::Generic(format!(
+                    "Content of {} bytes exceeds the limit of {} bytes",
+                    args.content.len(),
+                    context.config.max_write_bytes
+                )));
+            }
+
+            let path = context.jail.resolve(&args.path)?;
+            if path.is_dir() {
+                return Err(AgentError::Generic(format!("{} is a directory", args.path)));
+            }
+            let append = args.append.unwrap_or(false);
+            let existed = path.exists();
+            let result = json!({
+                "path": context.jail.display(&path),
+                "bytes": args.content.len(),
+                "existed": existed,
+                "append": append,
+                "dry_run": context.config.dry_run,
+            });
+            if context.config.dry_run {
+                return Ok(result);
+            }
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| io_error("write", &args.path, e))?;
+            }
+            open_for_write(&path, append, existed)
+                .and_then(|mut file| file.write_all(args.content.as_bytes()))
+                .map_err(|e| io_error("write", &args.path, e))?;
+
+            Ok(result)
+        })
+        .await
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SearchFilesArgs {
+    /// regular expression to search for, e.g. "fn \\w+_tool"
+    pub pattern: String,
+    /// directory to search, relative to the root directory, "." by default
+    pub path: Option<String>,
+    /// only search files whose name matches this wildcard pattern, e.g. "*.rs"
+    pub file_name: Option<String>,
+    /// ignore case, false by default
+    pub ignore_case: Option<bool>,
+}
+
+pub struct SearchFilesTool {
+    context: Arc<FsContext>,
+}
+
+/// Match a file name against a pattern with `*` and `?` wildcards
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matches[j]: the pattern read so far matches name[..j]
+    let mut matches = vec![false; name.len() + 1];
+    matches[0] = true;
+
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matches[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                c => j > 0 && matches[j - 1] && name[j - 1] == c,
+            };
+        }
+        matches = next;
+    }
+
+    matches[name.len()]
+}
+
+#[async_trait]
+impl ToolFunction for SearchFilesTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: SearchFilesArgs = parse_args(args)?;
+        let context = self.context.clone();
+
+        blocking(move || {
+            let regex = RegexBuilder::new(&args.pattern)
+                .case_insensitive(args.ignore_case.unwrap_or(false))
+                .build()
+                .map_err(|e| AgentError::Generic(format!("Invalid pattern: {}", e)))?;
+            let start = context.jail.resolve(args.path.as_deref().unwrap_or("."))?;
+            let max_results = context.config.max_results;
+
+            let mut matches = Vec::new();
+            let mut truncated = false;
+            // symlinks are not followed, so the walk stays inside the root
+            let mut pending = vec![start];
+            'walk: while let Some(path) = pending.pop() {
+                let Ok(metadata) = path.symlink_metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    let Ok(entries) = std::fs::read_dir(&path) else {
+                        continue;
+                    };
+                    let mut children: Vec<PathBuf> =
+                        entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+                    // popped from the end, so sort in reverse for a sorted walk
+                    children.sort_by(|a, b| b.cmp(a));
+                    pending.extend(children);
+                    continue;
+                }
+                if !metadata.is_file() || metadata.len() > context.config.max_read_bytes as u64 {
+                    continue;
+                }
+
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if let Some(pattern) = &args.file_name
+                    && !wildcard_match(pattern, &name)
+                {
+                    continue;
+                }
+                // binary files are skipped
+                let Ok(content) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+
+                for (index, line) in content.lines().enumerate() {
+                    if !regex.is_match(line) {
+                        continue;
+                    }
+                    if matches.len() == max_results {
+                        truncated = true;
+                        break 'walk;
+                    }
+                    matches.push(json!({
+                        "path": context.jail.display(&path),
+                        "line": index + 1,
+                        "text": line.chars().take(300).collect::<String>(),
+                    }));
+                }
+            }
+
+            Ok(json!({ "matches": matches, "truncated": truncated }))
+        })
+        .await
+    }
+}
+
+/// The `read_file`, `list_dir`, `write_file` and `search_files` tools confined to the root
+/// of `config`, with their policies. Writes need approval unless they are dry runs.
+pub fn fs_tools(config: FsConfig) -> Result<Vec<PolicyTool>> {
+    let dry_run = config.dry_run;
+    let context = Arc::new(FsContext {
+        jail: PathJail::new(&config.root)?,
+        config,
+    });
+
+    let write_policy = ToolPolicy {
+        risk: if dry_run {
+            RiskLevel::Low
+        } else {
+            RiskLevel::High
+        },
+        ..Default::default()
+    };
+
+    Ok(vec![
+        (
+            ToolDefinition::new::<ReadFileArgs>(
+                "read_file",
+                "Read a text file, long files are returned in chunks",
+            ),
+            Arc::new(ReadFileTool {
+                context: context.clone(),
+            }),
+            ToolPolicy::default(),
+        ),
+        (
+            ToolDefinition::new::<ListDirArgs>("list_dir", "List the entries of a directory"),
+            Arc::new(ListDirTool {
+                context: context.clone(),
+            }),
+            ToolPolicy::default(),
+        ),
+        (
+            ToolDefinition::new::<WriteFileArgs>(
+                "write_file",
+                "Create, replace or append to a file",
+            ),
+            Arc::new(WriteFileTool {
+                context: context.clone(),
+            }),
+            write_policy,
+        ),
+        (
+            ToolDefinition::new::<SearchFilesArgs>(
+                "search_files",
+                "Search the lines of the files in a directory, recursively",
+            ),
+            Arc::new(SearchFilesTool { context }),
+            ToolPolicy::default(),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("reactagent-fs-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(dir.join("src")).unwrap();
+            std::fs::write(dir.join("README.md"), "# Demo\nHello world\n").unwrap();
+            std::fs::write(
+                dir.join("src/main.rs"),
+                "fn main() {\n    println!(\"hello\");\n}\n",
+            )
+            .unwrap();
+            TempRoot(dir)
+        }
+
+        fn tools(&self, configure: impl FnOnce(&mut FsConfig)) -> Vec<Arc<dyn ToolFunction>> {
+            let mut config = FsConfig::new(&self.0);
+            configure(&mut config);
+            fs_tools(config)
+                .unwrap()
+                .into_iter()
+                .map(|(_, tool, _)| tool)
+                .collect()
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn match_wildcards() {
+        assert!(wildcard_match("*.rs", "main.rs"));
+        assert!(wildcard_match("ma?n.*", "main.rs"));
+        assert!(!wildcard_match("*.rs", "main.rs.bak"));
+    }
+
+    #[test]
+    fn jail_paths() {
+        let root = TempRoot::new("jail");
+        let jail = PathJail::new(&root.0).unwrap();
+
+        assert!(
+            jail.resolve("src/main.rs")
+                .unwrap()
+                .ends_with("src/main.rs")
+        );
+        assert!(jail.resolve("new/dir/file.txt").is_ok());
+        assert!(jail.resolve("../etc/passwd").is_err());
+        assert!(jail.resolve("src/../../etc").is_err());
+        assert!(jail.resolve("/etc/passwd").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc", root.0.join("etc")).unwrap();
+            assert!(jail.resolve("etc/passwd").is_err());
+            assert!(jail.resolve("etc/new_file").is_err());
+
+            let target =
+                std::env::temp_dir().join(format!("reactagent-fs-missing-{}", std::process::id()));
+            std::os::unix::fs::symlink(&target, root.0.join("out")).unwrap();
+            assert!(jail.resolve("out").is_err());
+            assert!(jail.resolve("out/file.txt").is_err());
+            assert!(!target.exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn read_list_and_search() {
+        let root = TempRoot::new("read");
+        let tools = root.tools(|config| config.max_read_bytes = 8);
+        let (read, list, search) = (&tools[0], &tools[1], &tools[3]);
+
+        let chunk = read.call(json!({"path": "README.md"})).await.unwrap();
+        assert_eq!(chunk["content"], "# Demo\nH");
+        assert_eq!(chunk["next_offset"], 8);
+        let rest = read
+            .call(json!({"path": "README.md", "offset": 8}))
+            .await
+            .unwrap();
+        assert_eq!(rest["content"], "ello wor");
+
+        let listing = list.call(json!({})).await.unwrap();
+        assert_eq!(listing["entries"][0]["name"], "README.md");
+        assert_eq!(listing["entries"][1]["type"], "dir");
+
+        // files above the read limit are not searched
+        let none = search.call(json!({"pattern": "hello"})).await.unwrap();
+        assert_eq!(none["matches"], json!([]));
+
+        let tools = root.tools(|_| {});
+        let found = tools[3]
+            .call(json!({"pattern": "HELLO", "ignore_case": true, "file_name": "*.rs"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            found["matches"],
+            json!([{"path": "src/main.rs", "line": 2, "text": "    println!(\"hello\");"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn write_files() {
+        let root = TempRoot::new("write");
+
+        let dry_run = root.tools(|config| config.dry_run = true);
+        let planned = dry_run[2]
+            .call(json!({"path": "notes/todo.md", "content": "- test"}))
+            .await
+            .unwrap();
+        assert_eq!(planned["dry_run"], true);
+        assert!(!root.0.join("notes").exists());
+
+        let tools = root.tools(|config| config.max_write_bytes = 10);
+        tools[2]
+            .call(json!({"path": "notes/todo.md", "content": "- a\n"}))
+            .await
+            .unwrap();
+        tools[2]
+            .call(json!({"path": "notes/todo.md", "content": "- b\n", "append": true}))
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.0.join("notes/todo.md")).unwrap(),
+            "- a\n- b\n"
+        );
+
+        let too_long = tools[2]
+            .call(json!({"path": "big.txt", "content": "0123456789ab"}))
+            .await;
+        assert!(too_long.is_err());
+        assert!(
+            tools[2]
+                .call(json!({"path": "../escape.txt", "content": "x"}))
+                .await
+                .is_err()
+        );
+    }
+}
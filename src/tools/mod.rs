@@ -1,3 +1,9 @@
+pub mod calc;
+pub mod fetch;
+pub mod fs;
+pub mod shell;
+pub mod sql;
+
 pub mod weather {
     use std::env;
 
@@ -10,10 +16,43 @@ pub mod weather {
     use serde_json::{Value, json};
 
     use crate::agent::tool::ToolFunction;
+    use crate::http::HttpContext;
+
+    #[derive(Default)]
+    pub struct GetWeatherTool {
+        http: HttpContext,
+    }
+
+    /// Temperature unit of measurement
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+    pub enum TemperatureUnit {
+        #[default]
+        #[serde(alias = "celsius", alias = "metric")]
+        Celsius,
+        #[serde(alias = "fahrenheit", alias = "imperial")]
+        Fahrenheit,
+    }
 
-    pub struct GetWeatherTool;
+    impl TemperatureUnit {
+        /// Value of the OpenWeatherMap `units` query parameter
+        pub fn provider_units(&self) -> &'static str {
+            match self {
+                TemperatureUnit::Celsius => "metric",
+                TemperatureUnit::Fahrenheit => "imperial",
+            }
+        }
+
+        /// Human readable label, e.g. "Celsius"
+        pub fn label(&self) -> &'static str {
+            match self {
+                TemperatureUnit::Celsius => "Celsius",
+                TemperatureUnit::Fahrenheit => "Fahrenheit",
+            }
+        }
+    }
 
     #[derive(Serialize, Deserialize, JsonSchema)]
+    #[schemars(example = "example_weather_args")]
     pub struct GetWeatherArgs {
         /// the name of the city
         pub city: String,
@@ -21,8 +60,18 @@ pub mod weather {
         pub longitude: f32,
         /// latitude of the location
         pub latitude: f32,
-        /// Unit of measurement - "Celsius" or "Fahrenheit", "Celsius" by default
-        pub unit: Option<String>,
+        /// Unit of measurement, "Celsius" by default
+        pub unit: Option<TemperatureUnit>,
+    }
+
+    fn example_weather_args() -> GetWeatherArgs {
+        GetWeatherArgs {
+            city: "London".to_string(),
+            // exactly representable in f32, so the example schema shows no rounding noise
+            longitude: -0.125,
+            latitude: 51.5,
+            unit: None,
+        }
     }
 
     #[derive(Serialize, Deserialize, JsonSchema)]
@@ -31,15 +80,20 @@ pub mod weather {
         pub city: String,
         /// current temperature in Celsius or Fahrenheit
         pub temperature: f32,
-        /// Unit of measurement - "Celsius" or "Fahrenheit"
-        pub unit: String,
+        /// Unit of measurement of the temperature
+        pub unit: TemperatureUnit,
         /// weather condition, e.g., "Sunny"
         pub condition: String,
     }
 
     impl GetWeatherTool {
+        pub fn new(http: HttpContext) -> Self {
+            GetWeatherTool { http }
+        }
+
         pub async fn get_weather(&self, args: GetWeatherArgs) -> Result<GetWeatherResponse> {
-            let client = reqwest::Client::new();
+            let client = self.http.client();
+            let unit = args.unit.unwrap_or_default();
 
             let url = "https://api.openweathermap.org/data/2.5/weather";
 
@@ -50,13 +104,7 @@ pub mod weather {
                     "appid",
                     env::var("OPENWEATHERMAP_API_KEY").unwrap_or_default(),
                 ),
-                (
-                    "units",
-                    args.unit
-                        .clone()
-                        .or_else(|| Some("metric".to_string()))
-                        .unwrap(),
-                ),
+                ("units", unit.provider_units().to_string()),
             ];
 
             let response = client.get(url).query(&params).send().await?;
@@ -72,7 +120,7 @@ pub mod weather {
                 AgentError::Generic(format!("Failed to parse JSON response: {}", e))
             })?;
 
-            println!(
+            eprintln!(
                 "3rd party API response (Open Weather Map): {}",
                 json_body.to_string().dimmed()
             );
@@ -80,7 +128,7 @@ pub mod weather {
             Ok(GetWeatherResponse {
                 city: args.city.clone(),
                 temperature: json_body["main"]["temp"].as_f64().unwrap_or(0.0) as f32,
-                unit: args.unit.clone().unwrap_or("Celsius".to_string()),
+                unit,
                 condition: json_body["weather"][0]["description"]
                     .as_str()
                     .unwrap_or("Unknown")
@@ -101,31 +149,32 @@ pub mod weather {
                 .get("longitude")
                 .and_then(|v| v.as_f64())
                 .unwrap_or(0.0);
-            let unit = args
-                .get("unit")
-                .and_then(|v| v.as_str())
-                .unwrap_or("metric");
+            let unit = match args.get("unit") {
+                None | Some(Value::Null) => None,
+                Some(v) => Some(
+                    serde_json::from_value::<TemperatureUnit>(v.clone()).map_err(|_| {
+                        AgentError::Generic(format!(
+                            "Unsupported unit: {}, expected \"Celsius\" or \"Fahrenheit\"",
+                            v
+                        ))
+                    })?,
+                ),
+            };
 
             let response = self
                 .get_weather(GetWeatherArgs {
                     city: city.to_string(),
                     latitude: latitude as f32,
                     longitude: longitude as f32,
-                    unit: Some(unit.to_string()),
+                    unit,
                 })
                 .await?;
 
-            let unit_translated = match unit {
-                "metric" => "Celsius",
-                "imperial" => "Fahrenheit",
-                _ => "Kelvin",
-            };
-
             Ok(json!({
                 "city": city,
                 "latitude": latitude,
                 "longitude": longitude,
-                "unit": unit_translated.to_string(),
+                "unit": response.unit.label(),
                 "temperature": response.temperature,
             }))
         }
@@ -144,15 +193,26 @@ pub mod geo {
     use serde_json::{Value, json};
 
     use crate::agent::tool::ToolFunction;
+    use crate::http::HttpContext;
 
-    pub struct GetGeoLocationTool;
+    #[derive(Default)]
+    pub struct GetGeoLocationTool {
+        http: HttpContext,
+    }
 
     #[derive(Serialize, Deserialize, JsonSchema)]
+    #[schemars(example = "example_geo_location_args")]
     pub struct GetGeoLocationArgs {
         /// the name of the city
         pub city: String,
     }
 
+    fn example_geo_location_args() -> GetGeoLocationArgs {
+        GetGeoLocationArgs {
+            city: "London".to_string(),
+        }
+    }
+
     pub struct GetGeoLocationResponse {
         /// the name of the city
         pub city: String,
@@ -163,11 +223,15 @@ pub mod geo {
     }
 
     impl GetGeoLocationTool {
+        pub fn new(http: HttpContext) -> Self {
+            GetGeoLocationTool { http }
+        }
+
         pub async fn get_geo_location(
             &self,
             args: GetGeoLocationArgs,
         ) -> Result<GetGeoLocationResponse> {
-            let client = reqwest::Client::new();
+            let client = self.http.client();
 
             let url = "https://api.opencagedata.com/geocode/v1/json";
 
@@ -190,7 +254,7 @@ pub mod geo {
                 AgentError::Generic(format!("Failed to parse JSON response: {}", e))
             })?;
 
-            println!(
+            eprintln!(
                 "3rd party API response (Open Cage Geo): {}",
                 json_body.to_string().dimmed()
             );
@@ -249,7 +313,7 @@ mod tests {
             unit: None,
         };
 
-        let result = block_on(weather::GetWeatherTool.get_weather(args));
+        let result = block_on(weather::GetWeatherTool::default().get_weather(args));
         if result.is_err() {
             eprintln!("Error: {}", result.as_ref().err().unwrap());
         }
@@ -261,6 +325,19 @@ mod tests {
         assert!(!weather.condition.is_empty());
     }
 
+    #[test]
+    fn test_temperature_unit() {
+        let unit: weather::TemperatureUnit = serde_json::from_str("\"Fahrenheit\"").unwrap();
+        assert_eq!(unit.provider_units(), "imperial");
+        assert_eq!(unit.label(), "Fahrenheit");
+
+        let unit: weather::TemperatureUnit = serde_json::from_str("\"metric\"").unwrap();
+        assert_eq!(unit, weather::TemperatureUnit::Celsius);
+        assert_eq!(unit.provider_units(), "metric");
+
+        assert!(serde_json::from_str::<weather::TemperatureUnit>("\"Kelvin\"").is_err());
+    }
+
     #[test]
     fn test_get_geo_location() {
         dotenv().unwrap();
@@ -269,7 +346,7 @@ mod tests {
             city: "London".to_string(),
         };
 
-        let result = block_on(geo::GetGeoLocationTool.get_geo_location(args));
+        let result = block_on(geo::GetGeoLocationTool::default().get_geo_location(args));
         if result.is_err() {
             eprintln!("Error: {}", result.as_ref().err().unwrap());
         }
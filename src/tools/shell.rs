@@ -0,0 +1,364 @@
+use std::{
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use crate::agent::{approval::RiskLevel, policy::ToolPolicy, tool::ToolFunction};
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+};
+
+/// Restrictions of the `run_shell` tool
+#[derive(Debug, Clone)]
+pub struct ShellConfig {
+    /// Directory commands run in. Arguments naming a path outside of it, absolute or through
+    /// `..`, are rejected, but symlinks inside of it are followed.
+    pub working_dir: PathBuf,
+    /// File names of the programs commands may run, e.g. "ls" or "git". Programs able to
+    /// run other programs, like `find -exec`, `env` or `xargs`, defeat the allowlist.
+    pub allowed_binaries: Vec<String>,
+    /// Environment variables passed to commands, all others are removed
+    pub env_allowlist: Vec<String>,
+    /// Commands running longer are killed
+    pub timeout: Duration,
+    /// Bytes of stdout and of stderr kept, the rest is dropped
+    pub max_output_bytes: usize,
+}
+
+impl ShellConfig {
+    pub fn new(working_dir: impl Into<PathBuf>, allowed_binaries: &[&str]) -> Self {
+        ShellConfig {
+            working_dir: working_dir.into(),
+            allowed_binaries: allowed_binaries.iter().map(|b| b.to_string()).collect(),
+            env_allowlist: ["PATH", "LANG", "LC_ALL", "TZ"]
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            timeout: Duration::from_secs(30),
+            max_output_bytes: 16 * 1024,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RunShellArgs {
+    /// the command line, e.g. "ls -la src"; it is not run by a shell, so pipes, redirections and variables are not supported, and paths must be relative
+    pub command: String,
+    /// directory to run in, relative to the working directory
+    pub cwd: Option<String>,
+}
+
+pub struct RunShellTool {
+    config: ShellConfig,
+}
+
+impl RunShellTool {
+    pub fn new(config: ShellConfig) -> Self {
+        RunShellTool { config }
+    }
+
+    /// Shell commands can change anything the process can, so every call needs approval
+    pub fn policy() -> ToolPolicy {
+        ToolPolicy {
+            // the tool enforces its own timeout
+            timeout: None,
+            risk: RiskLevel::High,
+            ..Default::default()
+        }
+    }
+
+    fn current_dir(&self, cwd: Option<&str>) -> Result<PathBuf> {
+        let root = self.config.working_dir.canonicalize().map_err(|e| {
+            AgentError::Generic(format!(
+                "Invalid working directory {}: {}",
+                self.config.working_dir.display(),
+                e
+            ))
+        })?;
+        let Some(cwd) = cwd else {
+            return Ok(root);
+        };
+
+        let dir = root
+            .join(cwd)
+            .canonicalize()
+            .map_err(|e| AgentError::Generic(format!("Invalid cwd {}: {}", cwd, e)))?;
+        if !dir.starts_with(&root) {
+            return Err(AgentError::Generic(format!(
+                "cwd {} is outside of the working directory",
+                cwd
+            )));
+        }
+        Ok(dir)
+    }
+
+    pub async fn run_shell(&self, args: RunShellArgs) -> Result<Value> {
+        let words = split_command(&args.command)?;
+        let (program, program_args) = words
+            .split_first()
+            .ok_or_else(|| AgentError::Generic("Empty command".to_string()))?;
+
+        // only bare names are accepted, so the program is looked up in the PATH
+        if program.contains('/') || !self.config.allowed_binaries.contains(program) {
+            return Err(AgentError::Generic(format!(
+                "Program {} is not allowed, allowed programs: {}",
+                program,
+                self.config.allowed_binaries.join(", ")
+            )));
+        }
+
+        for argument in program_args {
+            check_argument(argument)?;
+        }
+
+        let mut command = Command::new(program);
+        command
+            .args(program_args)
+            .current_dir(self.current_dir(args.cwd.as_deref())?)
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        for name in &self.config.env_allowlist {
+            if let Ok(value) = std::env::var(name) {
+                command.env(name, value);
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| AgentError::Generic(format!("Failed to run {}: {}", program, e)))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let limit = self.config.max_output_bytes;
+
+        let run = async {
+            let (stdout, stderr, status) = tokio::join!(
+                read_capped(stdout, limit),
+                read_capped(stderr, limit),
+                child.wait()
+            );
+            (stdout, stderr, status)
+        };
+        // the child is killed when it is dropped after the timeout
+        let ((stdout, stdout_truncated), (stderr, stderr_truncated), status) =
+            tokio::time::timeout(self.config.timeout, run)
+                .await
+                .map_err(|_| AgentError::Timeout("run_shell".to_string(), self.config.timeout))?;
+        let status = status
+            .map_err(|e| AgentError::Generic(format!("Failed to wait for {}: {}", program, e)))?;
+
+        Ok(json!({
+            // None when killed by a signal
+            "exit_code": status.code(),
+            "stdout": stdout,
+            "stderr": stderr,
+            "truncated": stdout_truncated || stderr_truncated,
+        }))
+    }
+}
+
+/// Read a stream to its end, keeping the first `limit` bytes as lossy UTF-8
+async fn read_capped(mut reader: impl AsyncRead + Unpin, limit: usize) -> (String, bool) {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buffer = [0u8; 8192];
+
+    // keep draining after the limit so the process does not block on a full pipe
+    while let Ok(n) = reader.read(&mut buffer).await {
+        if n == 0 {
+            break;
+        }
+        let room = limit.saturating_sub(kept.len());
+        kept.extend_from_slice(&buffer[..n.min(room)]);
+        truncated |= n > room;
+    }
+
+    (String::from_utf8_lossy(&kept).into_owned(), truncated)
+}
+
+/// Reject an argument naming a path outside of the working directory. Besides the argument
+/// itself the value of `--option=value` and of `-ovalue` could be a path.
+fn check_argument(argument: &str) -> Result<()> {
+    let option_value = argument.split_once('=').map(|(_, value)| value);
+    let short_value = argument
+        .strip_prefix('-')
+        .filter(|rest| !rest.starts_with('-'))
+        .and_then(|rest| rest.get(1..));
+    let escapes = |path: &str| {
+        let path = Path::new(path);
+        path.is_absolute()
+            || path.starts_with("~")
+            || path.components().any(|c| c == Component::ParentDir)
+    };
+
+    if [Some(argument), option_value, short_value]
+        .into_iter()
+        .flatten()
+        .any(escapes)
+    {
+        return Err(AgentError::Generic(format!(
+            "Argument {} is outside of the working directory, use a relative path inside of it",
+            argument
+        )));
+    }
+    Ok(())
+}
+
+/// Split a command line into words with POSIX-like quoting, shell operators are rejected
+pub fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(unterminated(command)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(unterminated(command)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(unterminated(command)),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(unterminated(command)),
+            },
+            '|' | '&' | ';' | '<' | '>' | '`' | '$' | '(' | ')' => {
+                return Err(AgentError::Generic(format!(
+                    "Shell operator {} is not supported, quote it to pass it as an argument",
+                    c
+                )));
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+    Ok(words)
+}
+
+fn unterminated(command: &str) -> AgentError {
+    AgentError::Generic(format!("Unterminated quote or escape in: {}", command))
+}
+
+#[async_trait]
+impl ToolFunction for RunShellTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: RunShellArgs = serde_json::from_value(args)
+            .map_err(|e| AgentError::Generic(format!("Invalid arguments: {}", e)))?;
+        self.run_shell(args).await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell(allowed: &[&str]) -> RunShellTool {
+        let mut config = ShellConfig::new(std::env::temp_dir(), allowed);
+        config.timeout = Duration::from_millis(500);
+        config.max_output_bytes = 100;
+        RunShellTool::new(config)
+    }
+
+    #[test]
+    fn split_quoted_words() {
+        assert_eq!(
+            split_command(r#"grep -n "fn main" 'src dir' a\ b"#).unwrap(),
+            ["grep", "-n", "fn main", "src dir", "a b"]
+        );
+        assert_eq!(split_command("echo '|'").unwrap(), ["echo", "|"]);
+        assert!(split_command("ls | wc -l").is_err());
+        assert!(split_command("echo $HOME").is_err());
+        assert!(split_command("echo 'open").is_err());
+    }
+
+    #[tokio::test]
+    async fn run_allowed_commands() {
+        let tool = shell(&["echo", "false", "env", "seq"]);
+
+        let echoed = tool.call(json!({"command": "echo hello"})).await.unwrap();
+        assert_eq!(echoed["exit_code"], 0);
+        assert_eq!(echoed["stdout"], "hello\n");
+
+        let failed = tool.call(json!({"command": "false"})).await.unwrap();
+        assert_eq!(failed["exit_code"], 1);
+
+        // cargo sets CARGO_* variables for the test process, they must not leak
+        let env = tool.call(json!({"command": "env"})).await.unwrap();
+        assert!(!env["stdout"].as_str().unwrap().contains("CARGO"));
+
+        let long = tool.call(json!({"command": "seq 1 10000"})).await.unwrap();
+        assert_eq!(long["truncated"], true);
+        assert_eq!(long["stdout"].as_str().unwrap().len(), 100);
+    }
+
+    #[tokio::test]
+    async fn reject_unsafe_commands() {
+        let tool = shell(&["echo", "sleep"]);
+
+        assert!(tool.call(json!({"command": "rm -rf /"})).await.is_err());
+        assert!(tool.call(json!({"command": "/bin/echo hi"})).await.is_err());
+        assert!(
+            tool.call(json!({"command": "echo hi", "cwd": ".."}))
+                .await
+                .is_err()
+        );
+        for command in [
+            "echo /etc/passwd",
+            "echo ../secret",
+            "echo a/../../secret",
+            "echo --file=/etc/passwd",
+            "echo -f/etc/passwd",
+            "echo ~/.ssh/id_rsa",
+        ] {
+            assert!(
+                tool.call(json!({ "command": command })).await.is_err(),
+                "{}",
+                command
+            );
+        }
+        let relative = tool.call(json!({"command": "echo -n a/b --x=c"})).await;
+        assert_eq!(relative.unwrap()["stdout"], "a/b --x=c");
+        assert!(matches!(
+            tool.call(json!({"command": "sleep 5"})).await,
+            Err(AgentError::Timeout(_, _))
+        ));
+    }
+}
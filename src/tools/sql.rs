@@ -0,0 +1,371 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::agent::{
+    approval::RiskLevel,
+    policy::ToolPolicy,
+    tool::{ToolDefinition, ToolFunction},
+};
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use rusqlite::{Connection, ErrorCode, OpenFlags, Statement, limits::Limit, types::ValueRef};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Settings of the `query_sql` tool
+#[derive(Debug, Clone)]
+pub struct SqlConfig {
+    /// SQLite database file, it must exist
+    pub path: PathBuf,
+    /// Allow statements changing the database or its schema, every call then needs approval
+    pub allow_writes: bool,
+    /// Rows returned by a query, the rest is dropped
+    pub max_rows: usize,
+    /// Characters kept of every value
+    pub max_cell_chars: usize,
+    /// Statements running longer are interrupted, waiting for another call is not counted
+    pub timeout: Duration,
+}
+
+impl SqlConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        SqlConfig {
+            path: path.into(),
+            allow_writes: false,
+            max_rows: 50,
+            max_cell_chars: 100,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct QuerySqlArgs {
+    /// a single SQLite statement, e.g. "SELECT city, avg(temp) FROM readings GROUP BY city"
+    pub query: String,
+}
+
+/// Runs SQL statements against a SQLite database and returns the rows as a compact table
+pub struct QuerySqlTool {
+    config: SqlConfig,
+    conn: Arc<Mutex<Connection>>,
+}
+
+fn sqlite_error(e: rusqlite::Error) -> AgentError {
+    AgentError::Generic(format!("SQLite error: {}", e))
+}
+
+impl QuerySqlTool {
+    pub fn open(config: SqlConfig) -> Result<Self> {
+        let flags = if config.allow_writes {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        };
+        let conn =
+            Connection::open_with_flags(&config.path, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+                .map_err(|e| {
+                    AgentError::Generic(format!(
+                        "Failed to open database {}: {}",
+                        config.path.display(),
+                        e
+                    ))
+                })?;
+        if !config.allow_writes {
+            // other database files could be attached and read otherwise
+            conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)
+                .map_err(sqlite_error)?;
+            conn.pragma_update(None, "query_only", true)
+                .map_err(sqlite_error)?;
+        }
+
+        Ok(QuerySqlTool {
+            config,
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Queries are read-only unless writes are allowed, which need approval
+    pub fn policy(&self) -> ToolPolicy {
+        ToolPolicy {
+            // run_query stops every statement at the configured timeout
+            timeout: None,
+            risk: if self.config.allow_writes {
+                RiskLevel::High
+            } else {
+                RiskLevel::Low
+            },
+            ..Default::default()
+        }
+    }
+
+    /// The tool definition, with the schema of the database in its description
+    pub fn definition(&self) -> Result<ToolDefinition> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT sql FROM sqlite_master
+                 WHERE type IN ('table', 'view') AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+                 ORDER BY name",
+            )
+            .map_err(sqlite_error)?;
+        let schema = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(sqlite_error)?
+            .map(|sql| {
+                // one line per table keeps the prompt short
+                sql.map(|sql| sql.split_whitespace().collect::<Vec<_>>().join(" ") + ";")
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(sqlite_error)?;
+
+        let access = if self.config.allow_writes {
+            "Run a SQLite statement"
+        } else {
+            "Run a read-only SQLite query"
+        };
+        Ok(ToolDefinition::new::<QuerySqlArgs>(
+            "query_sql",
+            &format!(
+                "{} and get at most {} rows as a table. Schema:\n{}",
+                access,
+                self.config.max_rows,
+                schema.join("\n")
+            ),
+        ))
+    }
+
+    pub async fn query_sql(&self, args: QuerySqlArgs) -> Result<Value> {
+        let conn = self.conn.clone();
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || run_query(&conn.lock().unwrap(), &config, &args.query))
+            .await
+            .map_err(|e| AgentError::Generic(format!("SQL task failed: {}", e)))?
+            .map(Value::String)
+    }
+}
+
+/// Number of SQLite VM instructions between checks of the deadline
+const PROGRESS_STEPS: i32 = 1000;
+
+/// Run a statement on the locked connection, interrupting it once it runs for longer than
+/// the timeout. The deadline starts here, so waiting for the lock does not count.
+fn run_query(conn: &Connection, config: &SqlConfig, query: &str) -> Result<String> {
+    let started = Instant::now();
+    // only the first statement would run, so a batch is rejected as a whole
+    let mut statement = conn.prepare(query).map_err(sqlite_error)?;
+    if !config.allow_writes && !statement.readonly() {
+        return Err(AgentError::Generic(
+            "Only read-only queries are allowed, the statement would change the database"
+                .to_string(),
+        ));
+    }
+
+    let timeout = config.timeout;
+    conn.progress_handler(PROGRESS_STEPS, Some(move || started.elapsed() > timeout));
+    let result = execute(&mut statement, config);
+    conn.progress_handler(0, None::<fn() -> bool>);
+
+    match result {
+        // the progress handler interrupted the statement
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == ErrorCode::OperationInterrupted && started.elapsed() > timeout =>
+        {
+            Err(AgentError::Timeout("query_sql".to_string(), timeout))
+        }
+        result => result.map_err(sqlite_error),
+    }
+}
+
+fn execute(statement: &mut Statement, config: &SqlConfig) -> rusqlite::Result<String> {
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    if columns.is_empty() {
+        let changed = statement.execute([])?;
+        return Ok(format!("OK, {} rows changed", changed));
+    }
+
+    let mut rows = statement.query([])?;
+    let mut table = vec![columns.join(" | ")];
+    let mut truncated = false;
+    while let Some(row) = rows.next()? {
+        if table.len() > config.max_rows {
+            truncated = true;
+            break;
+        }
+        let cells = (0..columns.len())
+            .map(|index| {
+                row.get_ref(index)
+                    .map(|value| format_cell(value, config.max_cell_chars))
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        table.push(cells.join(" | "));
+    }
+
+    let count = table.len() - 1;
+    table.push(if truncated {
+        format!(
+            "(first {} rows, add a LIMIT or aggregate to see the rest)",
+            count
+        )
+    } else {
+        format!("({} rows)", count)
+    });
+    Ok(table.join("\n"))
+}
+
+fn format_cell(value: ValueRef, max_chars: usize) -> String {
+    let text = match value {
+        ValueRef::Null => return "NULL".to_string(),
+        ValueRef::Integer(n) => return n.to_string(),
+        ValueRef::Real(n) => return n.to_string(),
+        ValueRef::Blob(blob) => return format!("<blob {} bytes>", blob.len()),
+        ValueRef::Text(text) => String::from_utf8_lossy(text),
+    };
+
+    // line breaks and separators in a value would break the table
+    let text = text.replace(['\n', '\r'], " ").replace('|', "/");
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
+
+#[async_trait]
+impl ToolFunction for QuerySqlTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args: QuerySqlArgs = serde_json::from_value(args)
+            .map_err(|e| AgentError::Generic(format!("Invalid arguments: {}", e)))?;
+        self.query_sql(args).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A database with a readings table, removed once the test is done
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "reactagent-sql-{}-{}.db",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            Connection::open(&path)
+                .unwrap()
+                .execute_batch(
+                    "CREATE TABLE readings (
+                        city TEXT NOT NULL,
+                        temp REAL,
+                        note TEXT
+                     );
+                     INSERT INTO readings VALUES
+                        ('Oslo', 21.5, NULL), ('Oslo', 18.5, 'rain | wind'), ('Paris', 25, NULL);",
+                )
+                .unwrap();
+            TempDb(path)
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn query_tables() {
+        let db = TempDb::new("query");
+        let tool = QuerySqlTool::open(SqlConfig {
+            max_rows: 2,
+            ..SqlConfig::new(&db.0)
+        })
+        .unwrap();
+
+        let definition = tool.definition().unwrap();
+        assert!(definition.description.ends_with(
+            "Schema:\nCREATE TABLE readings ( city TEXT NOT NULL, temp REAL, note TEXT );"
+        ));
+
+        let averages = tool
+            .call(json!({"query": "SELECT city, avg(temp) AS temp FROM readings GROUP BY city"}))
+            .await
+            .unwrap();
+        assert_eq!(averages, "city | temp\nOslo | 20\nParis | 25\n(2 rows)");
+
+        let all = tool
+            .call(json!({"query": "SELECT * FROM readings"}))
+            .await
+            .unwrap();
+        assert_eq!(
+            all,
+            "city | temp | note\nOslo | 21.5 | NULL\nOslo | 18.5 | rain / wind\n\
+             (first 2 rows, add a LIMIT or aggregate to see the rest)"
+        );
+    }
+
+    #[tokio::test]
+    async fn guard_writes() {
+        let db = TempDb::new("guard");
+        let read_only = QuerySqlTool::open(SqlConfig::new(&db.0)).unwrap();
+
+        for query in [
+            "DELETE FROM readings",
+            "DROP TABLE readings",
+            "CREATE TABLE t (x)",
+            "SELECT 1; DELETE FROM readings",
+            "ATTACH DATABASE ':memory:' AS other",
+        ] {
+            assert!(
+                read_only.call(json!({ "query": query })).await.is_err(),
+                "{}",
+                query
+            );
+        }
+
+        let writable = QuerySqlTool::open(SqlConfig {
+            allow_writes: true,
+            ..SqlConfig::new(&db.0)
+        })
+        .unwrap();
+        assert_eq!(writable.policy().risk, RiskLevel::High);
+        let deleted = writable
+            .call(json!({"query": "DELETE FROM readings WHERE city = 'Paris'"}))
+            .await
+            .unwrap();
+        assert_eq!(deleted, "OK, 1 rows changed");
+    }
+
+    #[tokio::test]
+    async fn interrupt_slow_queries() {
+        let db = TempDb::new("slow");
+        let tool = QuerySqlTool::open(SqlConfig {
+            timeout: Duration::from_millis(200),
+            ..SqlConfig::new(&db.0)
+        })
+        .unwrap();
+
+        // the count waits for the endless query, the wait does not count toward its timeout
+        let endless = "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) \
+                       SELECT count(*) FROM n";
+        let (endless, count) = tokio::join!(
+            tool.call(json!({ "query": endless })),
+            tool.call(json!({"query": "SELECT count(*) AS n FROM readings"}))
+        );
+        assert!(matches!(endless, Err(AgentError::Timeout(_, _))));
+        assert_eq!(count.unwrap(), "n\n3\n(1 rows)");
+    }
+}
@@ -0,0 +1,372 @@
+//! Tools compiled to WebAssembly, run in a sandbox.
+//!
+//! A plugin is a core module (plain or WASI preview 1) exporting:
+//! - `memory`
+//! - `alloc(len: i32) -> i32`, memory for the host to write the arguments to
+//! - `schema() -> i64`, JSON `{"name", "description", "parameters"}` of the tool
+//! - `call(ptr: i32, len: i32) -> i64`, takes the JSON arguments and returns
+//!   JSON `{"ok": <result>}` or `{"error": "<message>"}`
+//!
+//! Strings are returned packed as `ptr << 32 | len`. Every call runs in a fresh
+//! instance, WASI has no preopened directories, environment, arguments or sockets.
+
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use crate::agent::tool::{ToolDefinition, ToolFunction};
+use crate::error::AgentError;
+use crate::prelude::*;
+use async_trait::async_trait;
+use serde_json::Value;
+use wasmtime::{
+    Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap,
+};
+use wasmtime_wasi::{WasiCtxBuilder, preview1::WasiP1Ctx};
+
+/// Interval of the epoch ticker, the granularity of plugin timeouts
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Resource limits of a plugin, applied to every call
+#[derive(Debug, Clone)]
+pub struct WasmLimits {
+    /// Upper bound of the linear memory
+    pub max_memory_bytes: usize,
+    /// Wall time of a single call
+    pub timeout: Duration,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        WasmLimits {
+            max_memory_bytes: 16 * 1024 * 1024,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+struct PluginState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// Engine shared by all plugins, with a thread ticking its epoch
+fn engine() -> Result<&'static Engine> {
+    static ENGINE: OnceLock<std::result::Result<Engine, String>> = OnceLock::new();
+
+    ENGINE
+        .get_or_init(|| {
+            let mut config = Config::new();
+            config.epoch_interruption(true);
+            let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+
+            let ticking = engine.clone();
+            std::thread::spawn(move || {
+                loop {
+                    std::thread::sleep(EPOCH_TICK);
+                    ticking.increment_epoch();
+                }
+            });
+            Ok(engine)
+        })
+        .as_ref()
+        .map_err(|e| AgentError::Generic(format!("Failed to create WASM engine: {}", e)))
+}
+
+/// A tool implemented by a WASM plugin
+#[derive(Clone)]
+pub struct WasmTool {
+    name: String,
+    module: Module,
+    linker: Arc<Linker<PluginState>>,
+    limits: WasmLimits,
+}
+
+impl WasmTool {
+    /// Compile a plugin from a binary or text module and read its schema
+    pub fn load(bytes: &[u8], limits: WasmLimits) -> Result<(ToolDefinition, WasmTool)> {
+        let engine = engine()?;
+        let module = Module::new(engine, bytes)
+            .map_err(|e| AgentError::Generic(format!("Invalid WASM plugin: {}", e)))?;
+
+        let mut linker = Linker::new(engine);
+        wasmtime_wasi::preview1::add_to_linker_sync(&mut linker, |state: &mut PluginState| {
+            &mut state.wasi
+        })
+        .map_err(|e| AgentError::Generic(format!("Failed to link WASI: {}", e)))?;
+
+        let mut tool = WasmTool {
+            name: "plugin".to_string(),
+            module,
+            linker: Arc::new(linker),
+            limits,
+        };
+
+        let schema = tool.run(|store, instance| {
+            let schema = instance.get_typed_func::<(), i64>(&mut *store, "schema")?;
+            let packed = schema.call(&mut *store, ())?;
+            read_packed(store, instance, packed)
+        })?;
+        let schema: Value = serde_json::from_slice(&schema)
+            .map_err(|e| AgentError::Generic(format!("Invalid schema of WASM plugin: {}", e)))?;
+        let definition = ToolDefinition::from_schema(&schema)?;
+
+        tool.name = definition.name.clone();
+        Ok((definition, tool))
+    }
+
+    pub fn load_file(
+        path: impl AsRef<Path>,
+        limits: WasmLimits,
+    ) -> Result<(ToolDefinition, WasmTool)> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| {
+            AgentError::Generic(format!(
+                "Failed to read WASM plugin {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        WasmTool::load(&bytes, limits)
+    }
+
+    /// Run `f` on a fresh instance within the limits of the plugin
+    fn run<T>(
+        &self,
+        f: impl FnOnce(&mut Store<PluginState>, &Instance) -> wasmtime::Result<T>,
+    ) -> Result<T> {
+        let wasi = WasiCtxBuilder::new()
+            .allow_tcp(false)
+            .allow_udp(false)
+            .allow_ip_name_lookup(false)
+            .build_p1();
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.max_memory_bytes)
+            .build();
+
+        let mut store = Store::new(self.module.engine(), PluginState { wasi, limits });
+        store.limiter(|state| &mut state.limits);
+        let ticks = self.limits.timeout.as_millis() / EPOCH_TICK.as_millis();
+        store.set_epoch_deadline(ticks.max(1) as u64);
+        store.epoch_deadline_trap();
+
+        let result = self
+            .linker
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| {
+                // WASI reactors initialize themselves before any other export is called
+                if let Ok(initialize) = instance.get_typed_func::<(), ()>(&mut store, "_initialize")
+                {
+                    initialize.call(&mut store, ())?;
+                }
+                f(&mut store, &instance)
+            });
+
+        result.map_err(|e| match e.downcast_ref::<Trap>() {
+            Some(Trap::Interrupt) => AgentError::Timeout(self.name.clone(), self.limits.timeout),
+            _ => AgentError::Generic(format!("WASM plugin {} failed: {:#}", self.name, e)),
+        })
+    }
+
+    fn call_sync(&self, args: &Value) -> Result<Value> {
+        let input = args.to_string();
+        let output = self.run(|store, instance| {
+            let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "alloc")?;
+            let call = instance.get_typed_func::<(i32, i32), i64>(&mut *store, "call")?;
+            let memory = instance
+                .get_memory(&mut *store, "memory")
+                .ok_or_else(|| wasmtime::Error::msg("plugin exports no memory"))?;
+
+            let len = i32::try_from(input.len())?;
+            let ptr = alloc.call(&mut *store, len)?;
+            memory.write(&mut *store, ptr as u32 as usize, input.as_bytes())?;
+
+            let packed = call.call(&mut *store, (ptr, len))?;
+            read_packed(store, instance, packed)
+        })?;
+
+        let output: Value = serde_json::from_slice(&output).map_err(|e| {
+            AgentError::Generic(format!(
+                "WASM plugin {} returned invalid JSON: {}",
+                self.name, e
+            ))
+        })?;
+        match (output.get("ok"), output.get("error")) {
+            (_, Some(error)) => Err(AgentError::Generic(match error {
+                Value::String(message) => message.clone(),
+                other => other.to_string(),
+            })),
+            (Some(ok), None) => Ok(ok.clone()),
+            (None, None) => Err(AgentError::Generic(format!(
+                "WASM plugin {} returned neither \"ok\" nor \"error\"",
+                self.name
+            ))),
+        }
+    }
+}
+
+/// Bytes of a string returned packed as `ptr << 32 | len`
+fn read_packed(
+    store: &mut Store<PluginState>,
+    instance: &Instance,
+    packed: i64,
+) -> wasmtime::Result<Vec<u8>> {
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .ok_or_else(|| wasmtime::Error::msg("plugin exports no memory"))?;
+    let ptr = (packed as u64 >> 32) as usize;
+    let len = (packed as u64 & 0xffff_ffff) as usize;
+
+    memory
+        .data(&*store)
+        .get(ptr..ptr + len)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| wasmtime::Error::msg("returned string is out of bounds"))
+}
+
+/// Load every `*.wasm` plugin of a directory, sorted by file name
+pub fn load_plugins_dir(
+    dir: impl AsRef<Path>,
+    limits: WasmLimits,
+) -> Result<Vec<(ToolDefinition, WasmTool)>> {
+    let dir = dir.as_ref();
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        AgentError::Generic(format!(
+            "Failed to read plugins directory {}: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|e| e == "wasm"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| WasmTool::load_file(path, limits.clone()))
+        .collect()
+}
+
+#[async_trait]
+impl ToolFunction for WasmTool {
+    async fn call(&self, args: Value) -> Result<Value> {
+        let tool = self.clone();
+        tokio::task::spawn_blocking(move || tool.call_sync(&args))
+            .await
+            .map_err(|e| AgentError::Generic(format!("WASM task failed: {}", e)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCHEMA: &str =
+        r#"{"name":"echo","description":"Echo the arguments","parameters":{"type":"object"}}"#;
+
+    /// Text module of an `echo` plugin, `call_body` replaces the default body of `call`
+    fn plugin(imports: &str, memory_pages: u32, call_body: Option<&str>) -> String {
+        let echo = r#"
+            (local $out i32)
+            (local.set $out (call $alloc (i32.add (local.get $len) (i32.const 7))))
+            (memory.copy (local.get $out) (i32.const 512) (i32.const 6))
+            (memory.copy (i32.add (local.get $out) (i32.const 6)) (local.get $ptr) (local.get $len))
+            (i32.store8 (i32.add (local.get $out) (i32.add (local.get $len) (i32.const 6))) (i32.const 125))
+            (i64.or
+                (i64.shl (i64.extend_i32_u (local.get $out)) (i64.const 32))
+                (i64.extend_i32_u (i32.add (local.get $len) (i32.const 7))))"#;
+
+        format!(
+            r#"(module
+                {imports}
+                (memory (export "memory") {memory_pages})
+                (global $heap (mut i32) (i32.const 1024))
+                (data (i32.const 0) "{schema}")
+                (data (i32.const 512) "{{\"ok\":")
+                (func $alloc (export "alloc") (param $len i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $heap))
+                    (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+                    (local.get $ptr))
+                (func (export "schema") (result i64)
+                    (i64.const {schema_len}))
+                (func (export "call") (param $ptr i32) (param $len i32) (result i64)
+                    {body}))"#,
+            schema = SCHEMA.replace('"', "\\\""),
+            schema_len = SCHEMA.len(),
+            body = call_body.unwrap_or(echo),
+        )
+    }
+
+    #[tokio::test]
+    async fn call_plugin() {
+        let (definition, tool) =
+            WasmTool::load(plugin("", 1, None).as_bytes(), WasmLimits::default()).unwrap();
+        assert_eq!(definition.name, "echo");
+        assert_eq!(definition.description, "Echo the arguments");
+
+        let args = json!({"city": "Oslo"});
+        assert_eq!(tool.call(args.clone()).await.unwrap(), args);
+    }
+
+    #[tokio::test]
+    async fn enforce_limits() {
+        let limits = WasmLimits {
+            max_memory_bytes: 2 * 65536,
+            timeout: Duration::from_millis(50),
+        };
+
+        let too_large = plugin("", 4, None);
+        assert!(WasmTool::load(too_large.as_bytes(), limits.clone()).is_err());
+
+        let endless = plugin("", 1, Some("(loop $forever (br $forever)) (i64.const 0)"));
+        let (_, tool) = WasmTool::load(endless.as_bytes(), limits).unwrap();
+        assert!(matches!(
+            tool.call(json!({})).await,
+            Err(AgentError::Timeout(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn no_ambient_filesystem() {
+        // open "/etc/passwd" relative to fd 3, the first preopen if there were any,
+        // then return the errno as the single digit of {"ok":N}
+        let imports = r#"(import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+            (data (i32.const 600) "/etc/passwd")"#;
+        let body = r#"
+            (local $errno i32)
+            (local.set $errno (call $path_open (i32.const 3) (i32.const 0) (i32.const 600)
+                (i32.const 11) (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0)
+                (i32.const 700)))
+            (memory.copy (i32.const 800) (i32.const 512) (i32.const 6))
+            (i32.store8 (i32.const 806) (i32.add (i32.const 48) (local.get $errno)))
+            (i32.store8 (i32.const 807) (i32.const 125))
+            (i64.or (i64.shl (i64.const 800) (i64.const 32)) (i64.const 8))"#;
+
+        let (_, tool) = WasmTool::load(
+            plugin(imports, 1, Some(body)).as_bytes(),
+            WasmLimits::default(),
+        )
+        .unwrap();
+        // 8 is EBADF: there is no descriptor to open files from
+        assert_eq!(tool.call(json!({})).await.unwrap(), json!(8));
+    }
+
+    #[test]
+    fn reject_unknown_imports() {
+        let module = plugin(
+            r#"(import "env" "connect" (func (param i32) (result i32)))"#,
+            1,
+            None,
+        );
+        let result = WasmTool::load(module.as_bytes(), WasmLimits::default());
+        assert!(matches!(result, Err(AgentError::Generic(m)) if m.contains("connect")));
+    }
+}